@@ -4,8 +4,11 @@ require "asciidoctor"
 require "processor/spec"
 require "processor/sidenote"
 require "processor/document"
+require "processor/dependency"
 
 module Processor
+  OUTPUT_VERSION = 1
+
   def self.specs
     @@specs ||= []
     @@specs
@@ -19,12 +22,52 @@ module Processor
     @@document = value
   end
 
-  def self.process(source)
+  def self.reset
+    @@specs = []
+    @@dependencies = []
+    @@document = nil
+  end
+
+  def self.output(source)
+    Processor.reset
     Processor.read_file(source)
 
-    puts JSON.pretty_generate({
-      :document => Processor.document,
+    Processor.document.merge({
+      :version => OUTPUT_VERSION,
       :specs => Processor.specs,
+      :dependencies => Processor.dependencies,
     })
   end
+
+  def self.process(source)
+    puts JSON.pretty_generate(Processor.output(source))
+  end
+
+  # Handle one request per line on standard input, see "Processor servers" in
+  # the README.
+  def self.serve
+    $stdout.sync = true
+    puts JSON.generate({ :version => OUTPUT_VERSION })
+
+    $stdin.each_line do |line|
+      request = JSON.parse(line)
+      logger = Asciidoctor::MemoryLogger.new
+      Asciidoctor::LoggerManager.logger = logger
+
+      response = begin
+        Dir.chdir(request["root"]) do
+          { :output => Processor.output(request["path"]) }
+        end
+      rescue StandardError => e
+        { :error => e.full_message(highlight: false) }
+      end
+      response[:warnings] = logger.messages.map do |message|
+        text = message[:message]
+        text = text[:text] if text.is_a?(Hash)
+        "#{message[:severity]}: #{text}"
+      end
+
+      puts JSON.generate(response)
+    end
+  end
 end
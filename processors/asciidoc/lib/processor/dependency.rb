@@ -0,0 +1,41 @@
+require "pathname"
+
+module Processor
+  def self.dependencies
+    @@dependencies ||= []
+    @@dependencies
+  end
+
+  # Record a file relative to the site folder, which is the working
+  # directory of the processor.
+  def self.add_dependency(file)
+    dependency = Pathname.new(file).relative_path_from(Pathname.pwd).to_s
+    Processor.dependencies.push(dependency) unless Processor.dependencies.include?(dependency)
+  end
+
+  # Record every local file pulled in with `include::`, relative to the site
+  # folder, so that Eighty re-renders the document when one of them changes.
+  # Includes that do not resolve are never pushed, so they are recorded where
+  # they would be, for Eighty to re-render the document once they appear.
+  module IncludeTracker
+    def resolve_include_path(target, attrlist, attributes)
+      resolved = super
+
+      unless resolved.is_a?(Array) || Asciidoctor::Helpers.uriish?(target) || !@dir.is_a?(String)
+        Processor.add_dependency(File.expand_path(target, @dir))
+      end
+
+      resolved
+    end
+
+    def push_include(data, file = nil, path = nil, lineno = 1, attributes = {})
+      if file && !(file.start_with?("http://") || file.start_with?("https://"))
+        Processor.add_dependency(file)
+      end
+
+      super
+    end
+  end
+
+  Asciidoctor::PreprocessorReader.prepend(IncludeTracker)
+end
@@ -40,14 +40,17 @@ module Processor
     item[:title] = doc.attributes["doctitle"]
     item[:license] = doc.attributes["license"]
     item[:licenseCode] = doc.attributes["license-code"]
-    item[:author] = doc.attributes["author"]
-    item[:email] = doc.attributes["email"]
     item[:order] = doc.attributes["order"]
     if item[:order]
       item[:order] = Integer(item[:order])
     end
     item[:toc] = doc.converter.convert(doc, "outline", toclevels: 3)
-    item[:created] = doc.attributes["created"]
+    item[:metadata] = {
+      :author => doc.attributes["author"],
+      :email => doc.attributes["email"],
+      :created => doc.attributes["created"],
+      :noindex => doc.attr?("noindex") || nil,
+    }.compact
 
     description_block = Processor.search_description(doc.blocks)
     item[:description] = description_block.content.tr("\n", " ")
@@ -5,7 +5,7 @@ import re
 import glob
 import subprocess
 
-SOURCE = sys.argv[1]
+OUTPUT_VERSION = 1
 
 TOC_TEMPLATE = os.path.join(os.path.dirname(os.path.abspath(__file__)), "toc-template.html")
 
@@ -24,57 +24,99 @@ def parse_meta(pandoc_raw, name):
 
     return value.strip()
 
-file_path = SOURCE
-with open(file_path, "rb") as f:
-    content = f.read()
-
-if os.path.splitext(file_path)[1] == ".md":
-    pandoc_raw = json.loads(subprocess.run("pandoc -f markdown -t json {}".format(file_path), shell=True, check=True, capture_output=True).stdout)
-
-    title = parse_meta(pandoc_raw, "title")
-    sitemap_title = parse_meta(pandoc_raw, "sitemap")
-    document_id = parse_meta(pandoc_raw, "id")
-    description = parse_meta(pandoc_raw, "subtitle")
-    order = parse_meta(pandoc_raw, "order")
-
-    if not order is None:
-        order = int(order)
-
-    html = subprocess.run("pandoc -f markdown -t html {}".format(file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
-    toc = subprocess.run("pandoc --toc -f markdown -t html --template {} {}".format(TOC_TEMPLATE, file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
-
-    content = json.dumps({
-        "title": title,
-        "description": description,
-        "descriptionContent": description,
-        "sitemapTitle": sitemap_title,
-        "id": document_id,
-        "order": order,
-        "content": html,
-        "toc": toc,
-    }, sort_keys=True, indent=4)
-elif os.path.splitext(file_path)[1] == ".org":
-    pandoc_raw = json.loads(subprocess.run("pandoc -f org -t json {}".format(file_path), shell=True, check=True, capture_output=True).stdout)
-
-    title = parse_meta(pandoc_raw, "title")
-    description = parse_meta(pandoc_raw, "subtitle")
-    order = parse_meta(pandoc_raw, "order")
-
-    if not order is None:
-        order = int(order)
-
-    html = subprocess.run("pandoc -f org -t html {}".format(file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
-    toc = subprocess.run("pandoc --toc -f org -t html --template {} {}".format(TOC_TEMPLATE, file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
-
-    content = json.dumps({
-        "title": title,
-        "description": description,
-        "descriptionContent": description,
-        "order": order,
-        "content": html,
-        "toc": toc,
-    }, sort_keys=True, indent=4)
+def parse_noindex(pandoc_raw):
+    if not "noindex" in pandoc_raw["meta"]:
+        return False
+
+    if pandoc_raw["meta"]["noindex"]["t"] == "MetaBool":
+        return pandoc_raw["meta"]["noindex"]["c"]
+
+    return parse_meta(pandoc_raw, "noindex") in ["true", "t", "yes"]
+
+def parse_metadata(pandoc_raw):
+    metadata = {}
+
+    if parse_noindex(pandoc_raw):
+        metadata["noindex"] = True
+
+    return metadata
+
+def process(file_path):
+    if os.path.splitext(file_path)[1] == ".md":
+        pandoc_raw = json.loads(subprocess.run("pandoc -f markdown -t json {}".format(file_path), shell=True, check=True, capture_output=True).stdout)
+
+        title = parse_meta(pandoc_raw, "title")
+        sitemap_title = parse_meta(pandoc_raw, "sitemap")
+        document_id = parse_meta(pandoc_raw, "id")
+        description = parse_meta(pandoc_raw, "subtitle")
+        order = parse_meta(pandoc_raw, "order")
+        metadata = parse_metadata(pandoc_raw)
+
+        if not order is None:
+            order = int(order)
+
+        html = subprocess.run("pandoc -f markdown -t html {}".format(file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
+        toc = subprocess.run("pandoc --toc -f markdown -t html --template {} {}".format(TOC_TEMPLATE, file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
+
+        return {
+            "version": OUTPUT_VERSION,
+            "title": title,
+            "description": description,
+            "descriptionContent": description,
+            "sitemapTitle": sitemap_title,
+            "id": document_id,
+            "order": order,
+            "content": html,
+            "toc": toc,
+            "metadata": metadata,
+        }
+    elif os.path.splitext(file_path)[1] == ".org":
+        pandoc_raw = json.loads(subprocess.run("pandoc -f org -t json {}".format(file_path), shell=True, check=True, capture_output=True).stdout)
+
+        title = parse_meta(pandoc_raw, "title")
+        description = parse_meta(pandoc_raw, "subtitle")
+        order = parse_meta(pandoc_raw, "order")
+        metadata = parse_metadata(pandoc_raw)
+
+        if not order is None:
+            order = int(order)
+
+        html = subprocess.run("pandoc -f org -t html {}".format(file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
+        toc = subprocess.run("pandoc --toc -f org -t html --template {} {}".format(TOC_TEMPLATE, file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
+
+        return {
+            "version": OUTPUT_VERSION,
+            "title": title,
+            "description": description,
+            "descriptionContent": description,
+            "order": order,
+            "content": html,
+            "toc": toc,
+            "metadata": metadata,
+        }
+    else:
+        raise Exception("Unknown file extension")
+
+def serve():
+    # One request per line on standard input, see "Processor servers" in the
+    # README.
+    sys.stdout.write(json.dumps({"version": OUTPUT_VERSION}) + "\n")
+    sys.stdout.flush()
+
+    for line in sys.stdin:
+        request = json.loads(line)
+        try:
+            os.chdir(request["root"])
+            response = {"output": process(request["path"])}
+        except Exception as e:
+            message = str(e)
+            if isinstance(e, subprocess.CalledProcessError) and e.stderr:
+                message = e.stderr.decode("utf-8", "replace")
+            response = {"error": message}
+        sys.stdout.write(json.dumps(response) + "\n")
+        sys.stdout.flush()
+
+if sys.argv[1] == "--server":
+    serve()
 else:
-    raise "Unknown file extension"
-
-print(content)
+    print(json.dumps(process(sys.argv[1]), sort_keys=True, indent=4))
@@ -16,48 +16,83 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::Error;
+use crate::{workspace::WorkspaceChanges, Error, ErrorContext};
 use handlebars::Handlebars;
 use std::{
     collections::HashMap,
     fs,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 use walkdir::WalkDir;
 
 #[derive(Clone, Debug)]
 pub struct AssetStore {
-    pub assets: HashMap<PathBuf, Vec<u8>>,
+    pub assets: HashMap<PathBuf, Arc<Vec<u8>>>,
     pub handlebars: Handlebars<'static>,
 }
 
 impl AssetStore {
     pub fn new(root_path: &Path) -> Result<AssetStore, Error> {
-        let mut assets = HashMap::new();
+        Ok(AssetStore {
+            assets: load_assets(root_path)?,
+            handlebars: load_layouts(root_path)?,
+        })
+    }
+
+    /// Reload only layouts or only static assets, depending on what changed.
+    pub fn new_with_old(
+        root_path: &Path,
+        old: &AssetStore,
+        changes: &WorkspaceChanges,
+    ) -> Result<AssetStore, Error> {
+        let assets = if changes.static_assets() {
+            load_assets(root_path)?
+        } else {
+            old.assets.clone()
+        };
+        let handlebars = if changes.layouts() {
+            load_layouts(root_path)?
+        } else {
+            old.handlebars.clone()
+        };
+
+        Ok(AssetStore { assets, handlebars })
+    }
+}
 
-        let asset_path = root_path.join("_assets");
+fn load_assets(root_path: &Path) -> Result<HashMap<PathBuf, Arc<Vec<u8>>>, Error> {
+    let mut assets = HashMap::new();
 
-        let css_path = asset_path.join("css");
-        let font_path = asset_path.join("font");
-        let js_path = asset_path.join("js");
+    let asset_path = root_path.join("_assets");
 
-        for part_path in [css_path, font_path, js_path] {
-            for entry in WalkDir::new(&part_path) {
-                let entry = entry?;
+    let css_path = asset_path.join("css");
+    let font_path = asset_path.join("font");
+    let js_path = asset_path.join("js");
 
-                if entry.file_type().is_file() {
-                    let content = fs::read(entry.path())?;
-                    let rel_path = entry.path().strip_prefix(&asset_path)?;
+    for part_path in [css_path, font_path, js_path] {
+        for entry in WalkDir::new(&part_path) {
+            let entry = entry?;
 
-                    assets.insert(rel_path.to_owned(), content);
-                }
+            if entry.file_type().is_file() {
+                let content = fs::read(entry.path()).path_context(entry.path())?;
+                let rel_path = entry.path().strip_prefix(&asset_path)?;
+
+                assets.insert(rel_path.to_owned(), Arc::new(content));
             }
         }
+    }
+
+    Ok(assets)
+}
 
-        let mut handlebars = Handlebars::new();
-        handlebars.set_strict_mode(true);
-        handlebars.register_templates_directory(".hbs", asset_path.join("layouts"))?;
+fn load_layouts(root_path: &Path) -> Result<Handlebars<'static>, Error> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    let layout_path = root_path.join("_assets").join("layouts");
+    handlebars
+        .register_templates_directory(".hbs", &layout_path)
+        .path_context(&layout_path)?;
 
-        Ok(AssetStore { assets, handlebars })
-    }
+    Ok(handlebars)
 }
@@ -0,0 +1,286 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    document::{DocumentMetadata, RenderedData},
+    site::SiteMetadata,
+    Error,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// Bump whenever `RenderedData` or the key derivation changes.
+const CACHE_VERSION: &str = "5";
+const CACHE_FOLDER: &str = ".eighty-cache";
+
+/// Rendered documents stored under `.eighty-cache` in the workspace root,
+/// keyed by everything that goes into rendering them.
+pub struct RenderCache {
+    path: PathBuf,
+    used: Mutex<HashSet<String>>,
+    hits: AtomicUsize,
+}
+
+impl RenderCache {
+    pub fn new(root_path: &Path) -> Self {
+        Self {
+            path: root_path.join(CACHE_FOLDER),
+            used: Mutex::new(HashSet::new()),
+            hits: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn key(&self, site: &SiteMetadata, document: &DocumentMetadata) -> Result<String, Error> {
+        let mut hasher = Sha256::new();
+        let mut update = |part: &[u8]| {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        };
+
+        update(CACHE_VERSION.as_bytes());
+        update(env!("CARGO_PKG_VERSION").as_bytes());
+        // Going through `Value` sorts map keys, so that the hash is stable.
+        // Processors never see the site URLs, which `serve --local-urls`
+        // overrides, so they are left out to share entries with `build`.
+        let mut config = serde_json::to_value(&site.config)?;
+        if let Some(config) = config.as_object_mut() {
+            config.remove("url");
+            config.remove("baseUrl");
+        }
+        update(config.to_string().as_bytes());
+        update(
+            serde_json::to_value(&document.processor)?
+                .to_string()
+                .as_bytes(),
+        );
+        if let Some(processor) = &document.processor {
+            update(
+                processor
+                    .executable_stamp(&site.source_path)
+                    .unwrap_or_default()
+                    .as_bytes(),
+            );
+        }
+        update(document.rel_source_path.to_string_lossy().as_bytes());
+        update(&fs::read(&document.source_path)?);
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    pub fn get(&self, key: &str, site: &SiteMetadata) -> Result<Option<RenderedData>, Error> {
+        self.used
+            .lock()
+            .map_err(|_| Error::Poisoned)?
+            .insert(key.to_owned());
+
+        let data = fs::read(self.entry_path(key))
+            .ok()
+            .and_then(|content| serde_json::from_slice::<RenderedData>(&content).ok())
+            .filter(|data| data.is_current(&site.source_path));
+        if data.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Ok(data)
+    }
+
+    pub fn put(&self, key: &str, data: &RenderedData) -> Result<(), Error> {
+        fs::create_dir_all(&self.path)?;
+
+        // Write then rename, so that an interrupted build never leaves a
+        // truncated entry behind.
+        let temp_path = self.path.join(format!("{}.tmp", key));
+        fs::write(&temp_path, serde_json::to_vec(data)?)?;
+        fs::rename(&temp_path, self.entry_path(key))?;
+
+        Ok(())
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Remove entries that were not looked up since the cache was opened.
+    pub fn prune(&self) -> Result<(), Error> {
+        let used = self.used.lock().map_err(|_| Error::Poisoned)?;
+
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            let is_used = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(|stem| used.contains(stem) && path.extension() == Some("json".as_ref()))
+                .unwrap_or(false);
+
+            if !is_used {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.path.join(format!("{}.json", key))
+    }
+}
+
+pub fn digest(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        document::DocumentType,
+        processor::ProcessorConfig,
+        site::{SiteConfig, SiteName},
+    };
+    use std::{collections::HashMap, env, process, time::SystemTime};
+
+    struct Fixture {
+        root_path: PathBuf,
+        site: SiteMetadata,
+        document: DocumentMetadata,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root_path =
+                env::temp_dir().join(format!("eighty-cache-{}-{}", process::id(), name));
+            let _ = fs::remove_dir_all(&root_path);
+            fs::create_dir_all(&root_path).expect("temp dir is writable; qed");
+            fs::write(root_path.join("intro.md"), "Hello").expect("temp dir is writable; qed");
+            fs::write(root_path.join("render.sh"), "#!/bin/sh").expect("temp dir is writable; qed");
+
+            let config: SiteConfig = serde_json::from_value(serde_json::json!({
+                "title": "Example",
+                "url": "https://example.com/",
+                "baseUrl": "/",
+                "author": "Example",
+                "email": "",
+                "sitemap": { "enable": true, "depth": null },
+            }))
+            .expect("site config is valid; qed");
+            let processor: ProcessorConfig = serde_json::from_value(serde_json::json!({
+                "type": "markdown",
+                "command": "./render.sh",
+            }))
+            .expect("processor config is valid; qed");
+
+            Self {
+                site: SiteMetadata {
+                    name: SiteName("example".to_owned()),
+                    source_path: root_path.clone(),
+                    config,
+                    processors: HashMap::new(),
+                },
+                document: DocumentMetadata {
+                    typ: DocumentType::Markdown,
+                    processor: Some(processor),
+                    modified: SystemTime::now(),
+                    source_path: root_path.join("intro.md"),
+                    rel_source_path: PathBuf::from("intro.md"),
+                },
+                root_path,
+            }
+        }
+
+        fn key(&self) -> String {
+            RenderCache::new(&self.root_path)
+                .key(&self.site, &self.document)
+                .expect("fixture files exist; qed")
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root_path);
+        }
+    }
+
+    #[test]
+    fn key_is_stable() {
+        let fixture = Fixture::new("stable");
+
+        assert_eq!(fixture.key(), fixture.key());
+    }
+
+    #[test]
+    fn key_changes_with_source() {
+        let fixture = Fixture::new("source");
+        let key = fixture.key();
+
+        fs::write(&fixture.document.source_path, "Hello!").expect("temp dir is writable; qed");
+        assert_ne!(fixture.key(), key);
+    }
+
+    #[test]
+    fn key_changes_with_processor_config() {
+        let mut fixture = Fixture::new("processor");
+        let key = fixture.key();
+
+        if let Some(processor) = fixture.document.processor.as_mut() {
+            processor.args.push("--strict".to_owned());
+        }
+        assert_ne!(fixture.key(), key);
+    }
+
+    #[test]
+    fn key_changes_with_executable() {
+        let fixture = Fixture::new("executable");
+        let key = fixture.key();
+
+        fs::write(fixture.root_path.join("render.sh"), "#!/bin/sh\nexit 0")
+            .expect("temp dir is writable; qed");
+        assert_ne!(fixture.key(), key);
+    }
+
+    #[test]
+    fn key_changes_with_site_config() {
+        let mut fixture = Fixture::new("site-config");
+        let key = fixture.key();
+
+        fixture.site.config.title = "Other".to_owned();
+        assert_ne!(fixture.key(), key);
+    }
+
+    #[test]
+    fn key_ignores_site_urls() {
+        let mut fixture = Fixture::new("site-urls");
+        let key = fixture.key();
+
+        fixture.site.config.url = "http://localhost:8000/".to_owned();
+        fixture.site.config.base_url = "/example/".to_owned();
+        assert_eq!(fixture.key(), key);
+    }
+}
@@ -18,14 +18,18 @@
 
 use eighty::{
     workspace::{FullWorkspace, MetadatadWorkspace, RenderedWorkspace, SimplePostWorkspace},
-    Error,
+    Error, ErrorContext,
 };
 use std::{fs, path::Path};
 
-pub fn build(root_path: &Path, target_path: &Path) -> Result<(), Error> {
-    let metadatad = MetadatadWorkspace::new(&root_path)?;
+pub fn build(
+    root_path: &Path,
+    target_path: &Path,
+    allow_unresolved_xrefs: bool,
+) -> Result<(), Error> {
+    let metadatad = MetadatadWorkspace::new(root_path)?;
     let rendered = RenderedWorkspace::new(&metadatad)?;
-    let full = FullWorkspace::new(&rendered)?;
+    let full = FullWorkspace::new(&rendered, allow_unresolved_xrefs)?;
     let post = SimplePostWorkspace::new(&full)?;
 
     for (site_name, site) in &post.0 {
@@ -34,12 +38,11 @@ pub fn build(root_path: &Path, target_path: &Path) -> Result<(), Error> {
         for (file_path, file) in &site.files {
             let file_target_path = site_target_path.join(file_path);
 
-            fs::create_dir_all(
-                file_target_path
-                    .parent()
-                    .ok_or(Error::InvalidPathComponent)?,
-            )?;
-            fs::write(file_target_path, &file[..])?;
+            let file_target_folder = file_target_path
+                .parent()
+                .ok_or_else(|| Error::InvalidPathComponent(file_target_path.clone()))?;
+            fs::create_dir_all(file_target_folder).path_context(file_target_folder)?;
+            fs::write(&file_target_path, &file[..]).path_context(&file_target_path)?;
         }
     }
 
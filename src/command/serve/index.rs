@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use super::Context;
+use handlebars::html_escape;
+
+/// Page listing the served sites, for when no site was given.
+pub fn page(context: &Context) -> Vec<u8> {
+    let mut sites = context
+        .post
+        .keys()
+        .map(|name| {
+            let title = context
+                .full
+                .sites
+                .get(name)
+                .map(|site| site.site.config.title.clone())
+                .unwrap_or_else(|| "Specifications".to_string());
+
+            (name.0.clone(), title)
+        })
+        .collect::<Vec<_>>();
+    sites.sort();
+
+    let items = sites
+        .iter()
+        .map(|(name, title)| {
+            format!(
+                "<li><a href=\"/{}/\">{}</a> ({})</li>\n",
+                html_escape(name),
+                html_escape(title),
+                html_escape(name)
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "<!DOCTYPE html>\n<html><head><title>Sites</title></head><body>\n<ul>\n{}</ul>\n</body></html>\n",
+        items
+    )
+    .into_bytes()
+}
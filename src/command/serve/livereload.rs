@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use eighty::workspace::WorkspaceChanges;
+use hyper::{body::Bytes, Body, Response};
+use tokio::sync::broadcast;
+
+/// Server-sent events endpoint that browsers listen on for rebuilds.
+pub const ENDPOINT: &str = "/_eighty/livereload";
+
+const SCRIPT: &str = r#"<script>
+(function () {
+  var source = new EventSource("/_eighty/livereload");
+  source.addEventListener("reload", function (event) {
+    if (event.data === "css") {
+      document.querySelectorAll('link[rel="stylesheet"]').forEach(function (link) {
+        var url = new URL(link.href);
+        url.searchParams.set("eighty-reload", Date.now());
+        link.href = url.href;
+      });
+    } else {
+      window.location.reload();
+    }
+  });
+})();
+</script>
+"#;
+
+#[derive(Eq, Clone, Copy, PartialEq, Debug)]
+pub enum Reload {
+    Page,
+    Css,
+}
+
+impl Reload {
+    pub fn new(changes: Option<&WorkspaceChanges>) -> Self {
+        match changes {
+            Some(changes) if changes.stylesheets_only() => Reload::Css,
+            _ => Reload::Page,
+        }
+    }
+
+    fn event(self) -> String {
+        let data = match self {
+            Reload::Page => "page",
+            Reload::Css => "css",
+        };
+
+        format!("event: reload\ndata: {}\n\n", data)
+    }
+}
+
+/// Stream a reload event to the browser after each rebuild, until it
+/// disconnects.
+pub fn respond(reload: &broadcast::Sender<Reload>) -> Response<Body> {
+    let mut receiver = reload.subscribe();
+    let (mut sender, body) = Body::channel();
+
+    tokio::spawn(async move {
+        loop {
+            let reload = match receiver.recv().await {
+                Ok(reload) => reload,
+                Err(broadcast::error::RecvError::Lagged(_)) => Reload::Page,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            if sender.send_data(Bytes::from(reload.event())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Response::builder()
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .body(body)
+        .expect("static response parts are valid; qed")
+}
+
+/// Add the live reload script to an HTML page, before `</body>` if there is
+/// one.
+pub fn inject(content: &[u8]) -> Vec<u8> {
+    let content = String::from_utf8_lossy(content);
+
+    match content.rfind("</body>") {
+        Some(index) => format!("{}{}{}", &content[..index], SCRIPT, &content[index..]),
+        None => format!("{}{}", content, SCRIPT),
+    }
+    .into_bytes()
+}
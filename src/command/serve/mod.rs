@@ -0,0 +1,514 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+mod index;
+mod livereload;
+mod overlay;
+mod response;
+
+use self::livereload::Reload;
+use eighty::{
+    site::SiteName,
+    workspace::{
+        FullWorkspace, MetadatadWorkspace, RenderedWorkspace, SimplePostSite, SimplePostWorkspace,
+        WorkspaceChanges,
+    },
+    Error,
+};
+use hyper::{
+    header::{ALLOW, HOST},
+    server::conn::AddrIncoming,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{mpsc::channel, Arc, RwLock},
+    thread,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::broadcast;
+
+pub struct Context {
+    pub metadatad: MetadatadWorkspace,
+    pub rendered: RenderedWorkspace,
+    pub full: FullWorkspace,
+    pub post: SimplePostWorkspace,
+    pub site_name: Option<SiteName>,
+    /// When this build finished, the modification time of everything served.
+    pub built: SystemTime,
+}
+
+/// What is being served: the last successful build, and the error of the
+/// latest build if it failed.
+pub struct State {
+    pub context: Option<Context>,
+    pub error: Option<String>,
+}
+
+enum Route<'a> {
+    File(&'a SimplePostSite, PathBuf),
+    Index,
+    NotFound(Option<&'a SimplePostSite>),
+}
+
+/// Find the site a request is for. With a single site, every request goes to
+/// it. Otherwise, a `Host` header matching a site's url selects that site,
+/// and any other request is routed by its first path segment, which names
+/// the site.
+fn route<'a>(context: &'a Context, req: &Request<Body>) -> Result<Route<'a>, Error> {
+    let uri_path = Path::new(req.uri().path());
+
+    if let Some(site_name) = &context.site_name {
+        let site = context
+            .post
+            .get(site_name)
+            .ok_or_else(|| Error::SiteNotExist(site_name.clone()))?;
+
+        return Ok(match uri_path.strip_prefix(&site.base_url) {
+            Ok(rel_path) => Route::File(site, rel_path.to_owned()),
+            Err(_) => Route::NotFound(Some(site)),
+        });
+    }
+
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(strip_port);
+    if let Some(host) = host {
+        for (site_name, full_site) in &context.full.sites {
+            if url_host(&full_site.site.config.url) != Some(host) {
+                continue;
+            }
+
+            let site = context
+                .post
+                .get(site_name)
+                .ok_or_else(|| Error::SiteNotExist(site_name.clone()))?;
+
+            // Sites may share a host under different base urls.
+            if let Ok(rel_path) = uri_path.strip_prefix(&site.base_url) {
+                return Ok(Route::File(site, rel_path.to_owned()));
+            }
+        }
+    }
+
+    let mut components = uri_path.iter().skip(1);
+    let site = components
+        .next()
+        .and_then(|name| name.to_str())
+        .and_then(|name| context.post.get(&SiteName(name.to_string())));
+
+    Ok(match site {
+        Some(site) => Route::File(site, components.collect()),
+        None if uri_path == Path::new("/") => Route::Index,
+        None => Route::NotFound(None),
+    })
+}
+
+/// Host of a site url such as `https://example.org/`, without the port.
+fn url_host(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+
+    rest.split('/').next().map(strip_port)
+}
+
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => host,
+    }
+}
+
+async fn handle(
+    req: Request<Body>,
+    state: Arc<RwLock<State>>,
+    reload: broadcast::Sender<Reload>,
+) -> Result<Response<Body>, Error> {
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return Ok(Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(ALLOW, "GET, HEAD")
+            .body(Body::empty())?);
+    }
+
+    if req.uri().path() == livereload::ENDPOINT {
+        return Ok(livereload::respond(&reload));
+    }
+
+    let state = state.read()?;
+    let context = match &state.context {
+        Some(context) => context,
+        None => {
+            let error = state.error.as_deref().unwrap_or_default();
+
+            return response::content(
+                &req,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Path::new("index.html"),
+                livereload::inject(&overlay::page(error)),
+                SystemTime::now(),
+            );
+        }
+    };
+    let (site, rel_path) = match route(context, &req)? {
+        Route::File(site, rel_path) => (site, rel_path),
+        Route::Index => {
+            return response::content(
+                &req,
+                StatusCode::OK,
+                Path::new("index.html"),
+                livereload::inject(&index::page(context)),
+                context.built,
+            );
+        }
+        Route::NotFound(site) => return not_found(&req, &state, context, site),
+    };
+    let index_rel_path = rel_path.join("index.html");
+
+    let content = site
+        .files
+        .get(&rel_path)
+        .map(|content| (&rel_path, content))
+        .or_else(|| {
+            site.files
+                .get(&index_rel_path)
+                .map(|content| (&index_rel_path, content))
+        });
+
+    match content {
+        Some((content_path, content)) => response::content(
+            &req,
+            StatusCode::OK,
+            content_path,
+            page(&state, content_path, content),
+            context.built,
+        ),
+        None => not_found(&req, &state, context, Some(site)),
+    }
+}
+
+/// Serve the site's `404.html` if it has one.
+fn not_found(
+    req: &Request<Body>,
+    state: &State,
+    context: &Context,
+    site: Option<&SimplePostSite>,
+) -> Result<Response<Body>, Error> {
+    let path = Path::new("404.html");
+
+    match site.and_then(|site| site.files.get(path)) {
+        Some(content) => response::content(
+            req,
+            StatusCode::NOT_FOUND,
+            path,
+            page(state, path, content),
+            context.built,
+        ),
+        None => response::content(
+            req,
+            StatusCode::NOT_FOUND,
+            Path::new("404.txt"),
+            b"Not found".to_vec(),
+            context.built,
+        ),
+    }
+}
+
+/// Add the error overlay and live reload script to HTML files.
+fn page(state: &State, path: &Path, content: &[u8]) -> Vec<u8> {
+    if path.extension().and_then(|v| v.to_str()) != Some("html") {
+        return content.to_vec();
+    }
+
+    let content = match &state.error {
+        Some(error) => overlay::inject(content, error),
+        None => content.to_vec(),
+    };
+    livereload::inject(&content)
+}
+
+/// How the workspace is built and served.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Serve only this site, instead of all sites.
+    pub site_name: Option<SiteName>,
+    pub address: SocketAddr,
+    /// Point the url and base url of sites at the server, so that links
+    /// between pages and sites stay local.
+    pub local_urls: bool,
+    pub allow_unresolved_xrefs: bool,
+}
+
+impl Options {
+    /// Url and base url of a site when served locally.
+    fn local_urls(&self, site_name: &SiteName) -> (String, String) {
+        let authority = if self.address.ip().is_unspecified() {
+            format!("localhost:{}", self.address.port())
+        } else {
+            self.address.to_string()
+        };
+        let base_url = match self.site_name {
+            Some(_) => "/".to_string(),
+            None => format!("/{}/", site_name),
+        };
+
+        (format!("http://{}{}", authority, base_url), base_url)
+    }
+}
+
+#[tokio::main]
+pub async fn serve(root_path: &Path, options: Options) -> Result<(), Error> {
+    // Bind before building, so that a port in use is reported right away.
+    let incoming = AddrIncoming::bind(&options.address).map_err(|error| Error::Bind {
+        address: options.address,
+        error,
+    })?;
+
+    // A failed first build is shown in the browser like any other, so that
+    // it can be fixed without restarting.
+    let state = match async_build(root_path, options.clone()).await {
+        Ok(context) => State {
+            context: Some(context),
+            error: None,
+        },
+        Err(err) => {
+            eprintln!("error: {}", err);
+            State {
+                context: None,
+                error: Some(err.to_string()),
+            }
+        }
+    };
+    let state = Arc::new(RwLock::new(state));
+
+    let root_path = root_path.to_owned();
+    let watch_options = options.clone();
+    let watch_state = state.clone();
+    let (reload, _) = broadcast::channel(16);
+    let watch_reload = reload.clone();
+
+    thread::spawn(move || {
+        let watching = || -> Result<(), Error> {
+            let (tx, rx) = channel();
+
+            let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(2))?;
+            watcher.watch(root_path.clone(), RecursiveMode::Recursive)?;
+            let canonical_root_path = fs::canonicalize(&root_path)?;
+
+            // Changes since the last successful build, which a failed build
+            // leaves for the next one.
+            let mut pending_paths = Vec::new();
+            let mut pending_rescan = false;
+
+            loop {
+                let event = match rx.recv() {
+                    Ok(event) => event,
+                    Err(e) => {
+                        println!("watch error: {:?}", e);
+                        continue;
+                    }
+                };
+
+                // Handle everything that arrived together as one rebuild.
+                let mut events = vec![event];
+                events.extend(rx.try_iter());
+
+                let mut changed = false;
+                for event in events {
+                    let paths = match event {
+                        // Always followed by the debounced event for the path.
+                        DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) => {
+                            Vec::new()
+                        }
+                        DebouncedEvent::Create(path)
+                        | DebouncedEvent::Write(path)
+                        | DebouncedEvent::Chmod(path)
+                        | DebouncedEvent::Remove(path) => vec![path],
+                        DebouncedEvent::Rename(p1, p2) => vec![p1, p2],
+                        DebouncedEvent::Rescan => {
+                            pending_rescan = true;
+                            changed = true;
+                            Vec::new()
+                        }
+                        // Events may have been lost, so treat it like a rescan.
+                        DebouncedEvent::Error(err, _) => {
+                            println!("watch error: {}", Error::Notify(err));
+                            pending_rescan = true;
+                            changed = true;
+                            Vec::new()
+                        }
+                    };
+
+                    for path in paths {
+                        if let Some(path) = changed_path(&path, &canonical_root_path) {
+                            pending_paths.push(path);
+                            changed = true;
+                        }
+                    }
+                }
+
+                if !changed {
+                    continue;
+                }
+
+                pending_paths.sort();
+                pending_paths.dedup();
+                let changes = WorkspaceChanges::new(pending_paths.clone());
+
+                // A panic while holding the lock leaves the state as it was,
+                // so keep using it.
+                let result = {
+                    let state = watch_state.read().unwrap_or_else(|err| {
+                        println!("watch error: {}", err);
+                        err.into_inner()
+                    });
+                    let old = match &state.context {
+                        Some(context) if !pending_rescan => Some((context, &changes)),
+                        _ => None,
+                    };
+
+                    build(&root_path, &watch_options, old)
+                };
+
+                let mut state = watch_state.write().unwrap_or_else(|err| {
+                    println!("watch error: {}", err);
+                    err.into_inner()
+                });
+                match result {
+                    Ok(context) => {
+                        state.context = Some(context);
+                        state.error = None;
+
+                        let _ = watch_reload.send(Reload::new(if pending_rescan {
+                            None
+                        } else {
+                            Some(&changes)
+                        }));
+
+                        println!(
+                            "[workspace] rebuilt after changes to {} path(s)",
+                            changes.len()
+                        );
+                        pending_paths.clear();
+                        pending_rescan = false;
+                    }
+                    Err(err) => {
+                        eprintln!("error: {}", err);
+                        state.error = Some(err.to_string());
+
+                        let _ = watch_reload.send(Reload::Page);
+                    }
+                }
+            }
+        };
+
+        match watching() {
+            Ok(()) => println!("watching thread returned"),
+            Err(e) => println!("watching thread error: {}", e),
+        }
+    });
+
+    let make_svc = make_service_fn(move |_conn| {
+        let state = state.clone();
+        let reload = reload.clone();
+
+        async move {
+            Ok::<_, Error>(service_fn(move |req| {
+                handle(req, state.clone(), reload.clone())
+            }))
+        }
+    });
+
+    let server = Server::builder(incoming).serve(make_svc);
+
+    println!("[server] listening on http://{}/", options.address);
+    if let Err(e) = server.await {
+        eprintln!("server error: {}", e);
+    }
+
+    Ok(())
+}
+
+async fn async_build(root_path: &Path, options: Options) -> Result<Context, Error> {
+    let root_path = root_path.to_owned();
+
+    let context = tokio::task::spawn_blocking(move || -> Result<_, Error> {
+        build(&root_path, &options, None)
+    })
+    .await??;
+
+    Ok(context)
+}
+
+fn build(
+    root_path: &Path,
+    options: &Options,
+    old: Option<(&Context, &WorkspaceChanges)>,
+) -> Result<Context, Error> {
+    let allow_unresolved_xrefs = options.allow_unresolved_xrefs;
+
+    let (metadatad, rendered, full) = if let Some((old, changes)) = old {
+        let mut metadatad = MetadatadWorkspace::new_with_old(root_path, &old.metadatad, changes)?;
+        if options.local_urls {
+            metadatad.override_urls(|site_name| options.local_urls(site_name));
+        }
+        let rendered = RenderedWorkspace::new_with_old(&metadatad, &old.rendered)?;
+        let full =
+            FullWorkspace::new_with_old(&rendered, &old.full, changes, allow_unresolved_xrefs)?;
+        (metadatad, rendered, full)
+    } else {
+        let mut metadatad = MetadatadWorkspace::new(root_path)?;
+        if options.local_urls {
+            metadatad.override_urls(|site_name| options.local_urls(site_name));
+        }
+        let rendered = RenderedWorkspace::new(&metadatad)?;
+        let full = FullWorkspace::new(&rendered, allow_unresolved_xrefs)?;
+        (metadatad, rendered, full)
+    };
+    let post = SimplePostWorkspace::new(&full)?;
+
+    let context = Context {
+        metadatad,
+        rendered,
+        full,
+        post,
+        site_name: options.site_name.clone(),
+        built: SystemTime::now(),
+    };
+
+    Ok(context)
+}
+
+/// Path relative to the workspace root, unless it is hidden, such as the
+/// render cache or editor swap files, or outside the root.
+fn changed_path(path: &Path, canonical_root_path: &Path) -> Option<PathBuf> {
+    let rel_path = path.strip_prefix(canonical_root_path).ok()?;
+    if rel_path
+        .iter()
+        .any(|label| label.to_str().map(|l| l.starts_with(".")).unwrap_or(false))
+    {
+        return None;
+    }
+
+    Some(rel_path.to_owned())
+}
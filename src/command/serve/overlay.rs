@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use handlebars::html_escape;
+
+const STYLE: &str = "position: fixed; inset: 0; z-index: 2147483647; overflow: auto; \
+    margin: 0; padding: 2em; background: rgba(20, 20, 20, 0.95); color: #f8f8f8; \
+    font: 14px/1.5 monospace; white-space: pre-wrap;";
+
+fn overlay(error: &str) -> String {
+    format!(
+        "<div id=\"eighty-error\" style=\"{}\"><strong>Build failed</strong>, \
+         showing the last successful build.\n\n{}</div>\n",
+        STYLE,
+        html_escape(error)
+    )
+}
+
+/// Cover an HTML page with the error of the latest build.
+pub fn inject(content: &[u8], error: &str) -> Vec<u8> {
+    let content = String::from_utf8_lossy(content);
+    let overlay = overlay(error);
+
+    match content.rfind("</body>") {
+        Some(index) => format!("{}{}{}", &content[..index], overlay, &content[index..]),
+        None => format!("{}{}", content, overlay),
+    }
+    .into_bytes()
+}
+
+/// Error page for when there is no successful build to show.
+pub fn page(error: &str) -> Vec<u8> {
+    format!(
+        "<!DOCTYPE html>\n<html><head><title>Build failed</title></head><body>\n{}</body></html>\n",
+        overlay(error)
+    )
+    .into_bytes()
+}
@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use eighty::{cache, Error};
+use httpdate::HttpDate;
+use hyper::{
+    header::{
+        CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+        LAST_MODIFIED,
+    },
+    Body, Method, Request, Response, StatusCode,
+};
+use std::{path::Path, time::SystemTime};
+
+/// Content type of a served file, from its extension.
+pub fn content_type(path: &Path) -> String {
+    match mime_guess::from_path(path).first_raw() {
+        Some(mime) if mime.starts_with("text/") => format!("{}; charset=utf-8", mime),
+        Some(mime) => mime.to_string(),
+        None => "application/octet-stream".to_string(),
+    }
+}
+
+/// Respond with `content` as the file at `path`, or with 304 Not Modified if
+/// the client already has it.
+pub fn content(
+    req: &Request<Body>,
+    status: StatusCode,
+    path: &Path,
+    content: Vec<u8>,
+    modified: SystemTime,
+) -> Result<Response<Body>, Error> {
+    let etag = format!("\"{}\"", &cache::digest(&content)[..16]);
+    let modified = HttpDate::from(modified);
+
+    let response = Response::builder()
+        .header(ETAG, &etag)
+        .header(LAST_MODIFIED, modified.to_string())
+        // Always revalidate, as files change with every rebuild.
+        .header(CACHE_CONTROL, "no-cache");
+
+    if status == StatusCode::OK && is_fresh(req, &etag, modified) {
+        return Ok(response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())?);
+    }
+
+    let response = response
+        .status(status)
+        .header(CONTENT_TYPE, content_type(path))
+        .header(CONTENT_LENGTH, content.len());
+
+    if req.method() == Method::HEAD {
+        Ok(response.body(Body::empty())?)
+    } else {
+        Ok(response.body(content.into())?)
+    }
+}
+
+/// Whether the client's copy is current. `If-None-Match` takes precedence
+/// over `If-Modified-Since`.
+fn is_fresh(req: &Request<Body>, etag: &str, modified: HttpDate) -> bool {
+    let header = |name| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+
+    if let Some(if_none_match) = header(&IF_NONE_MATCH) {
+        return if_none_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == etag || tag == "*");
+    }
+
+    header(&IF_MODIFIED_SINCE)
+        .and_then(|since| since.parse::<HttpDate>().ok())
+        .map(|since| modified <= since)
+        .unwrap_or(false)
+}
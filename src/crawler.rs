@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! Files for search engines: `sitemap.xml` and `robots.txt`.
+
+use crate::date::w3c_datetime;
+use handlebars::html_escape;
+use std::time::SystemTime;
+
+/// Page listed in `sitemap.xml`.
+#[derive(Eq, Clone, PartialEq, Debug)]
+pub struct SitemapEntry {
+    pub url: String,
+    pub modified: Option<SystemTime>,
+}
+
+pub fn sitemap_xml(entries: &[SitemapEntry]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+
+    for entry in entries {
+        xml.push_str("  <url>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", html_escape(&entry.url)));
+        if let Some(modified) = entry.modified {
+            xml.push_str(&format!(
+                "    <lastmod>{}</lastmod>\n",
+                w3c_datetime(modified)
+            ));
+        }
+        xml.push_str("  </url>\n");
+    }
+
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+pub fn robots_txt(disallow: &[String], sitemap_url: Option<&str>) -> String {
+    let mut robots = String::from("User-agent: *\n");
+
+    if disallow.is_empty() {
+        robots.push_str("Disallow:\n");
+    }
+    for path in disallow {
+        robots.push_str(&format!("Disallow: {}\n", path));
+    }
+
+    if let Some(sitemap_url) = sitemap_url {
+        robots.push_str(&format!("\nSitemap: {}\n", sitemap_url));
+    }
+
+    robots
+}
@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! Dates in the formats of sitemaps and feeds, in UTC.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Format a time as `2021-05-01T12:00:00Z`.
+pub fn w3c_datetime(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Start of a `2021-05-01` date, in UTC.
+pub fn parse_date(date: &str) -> Option<SystemTime> {
+    let mut parts = date.split('-').map(|part| part.parse::<u32>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let days = days_from_civil(year as i64, month, day);
+    let secs = u64::try_from(days).ok()? * 86400;
+
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Number of days since 1970-01-01 of a calendar date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// Calendar date of a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_from_days_epoch() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn civil_from_days_leap_days() {
+        assert_eq!(civil_from_days(789), (1972, 2, 29));
+        assert_eq!(civil_from_days(18321), (2020, 2, 29));
+        // 1900 and 2100 are not leap years.
+        assert_eq!(civil_from_days(-25508), (1900, 3, 1));
+        assert_eq!(civil_from_days(47541), (2100, 3, 1));
+    }
+
+    #[test]
+    fn civil_from_days_2000_02_29() {
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+    }
+
+    #[test]
+    fn w3c_datetime_format() {
+        let time = UNIX_EPOCH + Duration::from_secs(1619872205);
+        assert_eq!(w3c_datetime(time), "2021-05-01T12:30:05Z");
+    }
+
+    #[test]
+    fn days_from_civil_round_trip() {
+        for days in [-25508, -1, 0, 789, 11016, 11017, 18321, 47541] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn parse_date_start_of_day() {
+        assert_eq!(
+            parse_date("2000-02-29"),
+            Some(UNIX_EPOCH + Duration::from_secs(11016 * 86400))
+        );
+        assert_eq!(
+            parse_date("2022-05-01").map(httpdate::fmt_http_date),
+            Some("Sun, 01 May 2022 00:00:00 GMT".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_date_invalid() {
+        assert_eq!(parse_date("2022-13-01"), None);
+        assert_eq!(parse_date("2022-05"), None);
+        assert_eq!(parse_date("1969-12-31"), None);
+        assert_eq!(parse_date("not-a-date"), None);
+    }
+}
@@ -18,28 +18,36 @@
 
 use crate::Error;
 use serde::{Deserialize, Serialize};
-use std::{path::Path, process::Command};
+use serde_json::{Map, Value};
+use std::{fs::File, io::BufReader, path::Path};
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct OrgOutput {
+pub struct JsonDocOutput {
+    pub id: Option<String>,
     pub title: String,
+    pub sitemap_title: Option<String>,
     pub description: String,
-    pub description_content: String,
+    pub description_content: Option<String>,
     pub order: Option<usize>,
+    pub toc: Option<String>,
     pub content: String,
-    pub toc: String,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
 }
 
-pub fn process_org(site_path: &Path, rel_path: &Path) -> Result<OrgOutput, Error> {
-    let output = Command::new("eighty-pandoc")
-        .arg(rel_path)
-        .current_dir(site_path)
-        .output()?;
+/// Whether the bundled document is named `*.html.jsondoc`, in which case its
+/// content is HTML rather than plaintext.
+pub fn is_html(rel_path: &Path) -> bool {
+    rel_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(|stem| stem.ends_with(".html"))
+        .unwrap_or(false)
+}
 
-    if !output.status.success() {
-        return Err(Error::RunCommandFailed);
-    }
+pub fn process_jsondoc(site_path: &Path, rel_path: &Path) -> Result<JsonDocOutput, Error> {
+    let file = File::open(site_path.join(rel_path))?;
 
-    Ok(serde_json::from_slice(&output.stdout)?)
+    Ok(serde_json::from_reader(BufReader::new(file))?)
 }
@@ -16,32 +16,312 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::Error;
-use serde::{Deserialize, Serialize};
-use std::{path::Path, process::Command};
-
-#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct MarkdownOutput {
-    pub id: Option<String>,
-    pub title: String,
-    pub sitemap_title: Option<String>,
-    pub description: String,
-    pub description_content: String,
-    pub order: Option<usize>,
-    pub content: String,
-    pub toc: String,
-}
-
-pub fn process_markdown(site_path: &Path, rel_path: &Path) -> Result<MarkdownOutput, Error> {
-    let output = Command::new("eighty-pandoc")
-        .arg(rel_path)
-        .current_dir(site_path)
-        .output()?;
-
-    if !output.status.success() {
-        return Err(Error::RunCommandFailed);
-    }
-
-    Ok(serde_json::from_slice(&output.stdout)?)
+use crate::{
+    processor::{ProcessorOutput, OUTPUT_VERSION},
+    Error,
+};
+use handlebars::html_escape;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+const TOC_DEPTH: usize = 3;
+
+struct Heading {
+    level: usize,
+    id: String,
+    text: String,
+}
+
+/// Render a Markdown document in-process. Front matter is a `---` block of
+/// `key: value` lines, taking the same `title`, `subtitle`, `id`, `order` and
+/// `sitemap` keys as the pandoc processor. Other keys become metadata.
+pub fn process_markdown(site_path: &Path, rel_path: &Path) -> Result<ProcessorOutput, Error> {
+    let source = fs::read_to_string(site_path.join(rel_path))?;
+    let (front_matter, body) = split_front_matter(&source);
+
+    let order = front_matter
+        .get("order")
+        .map(|order| {
+            order.parse::<usize>().map_err(|_| {
+                Error::InvalidProcessorOutput(format!("order {:?} is not a number", order))
+            })
+        })
+        .transpose()?;
+    let (description, description_content) = match front_matter.get("subtitle") {
+        Some(subtitle) => (plain_text(subtitle), Some(inline_html(subtitle))),
+        None => (String::new(), None),
+    };
+
+    let mut events = Parser::new_ext(body, options()).collect::<Vec<_>>();
+    let headings = assign_heading_ids(&mut events);
+
+    let mut content = String::new();
+    html::push_html(&mut content, events.into_iter());
+
+    let output = ProcessorOutput {
+        version: OUTPUT_VERSION,
+        id: front_matter.get("id").cloned(),
+        title: front_matter
+            .get("title")
+            .map(|title| plain_text(title))
+            .unwrap_or_default(),
+        sitemap_title: front_matter.get("sitemap").map(|title| plain_text(title)),
+        description,
+        description_content,
+        order,
+        toc: toc(&headings),
+        content,
+        license: None,
+        license_code: None,
+        specs: Vec::new(),
+        dependencies: Vec::new(),
+        metadata: front_matter
+            .iter()
+            .filter(|(key, _)| !RESERVED_KEYS.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), metadata_value(value)))
+            .collect(),
+    };
+    output.validate()?;
+
+    Ok(output)
+}
+
+const RESERVED_KEYS: &[&str] = &["title", "subtitle", "id", "order", "sitemap"];
+
+fn metadata_value(value: &str) -> Value {
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::String(value.to_owned()),
+    }
+}
+
+fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_SMART_PUNCTUATION
+        | Options::ENABLE_HEADING_ATTRIBUTES
+}
+
+fn split_front_matter(source: &str) -> (HashMap<String, String>, &str) {
+    let mut front_matter = HashMap::new();
+
+    let rest = match source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))
+    {
+        Some(rest) => rest,
+        None => return (front_matter, source),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+
+        let line = line.trim_end();
+        if line == "---" || line == "..." {
+            return (front_matter, &rest[offset..]);
+        }
+
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .or_else(|| {
+                    value
+                        .strip_prefix('\'')
+                        .and_then(|value| value.strip_suffix('\''))
+                })
+                .unwrap_or(value);
+            front_matter.insert(key.trim().to_owned(), value.to_owned());
+        }
+    }
+
+    // An unterminated block is not front matter.
+    (HashMap::new(), source)
+}
+
+fn plain_text(markdown: &str) -> String {
+    collect_text(&Parser::new_ext(markdown, options()).collect::<Vec<_>>())
+        .trim()
+        .to_owned()
+}
+
+fn inline_html(markdown: &str) -> String {
+    let mut content = String::new();
+    html::push_html(&mut content, Parser::new_ext(markdown, options()));
+
+    let content = content.trim();
+    content
+        .strip_prefix("<p>")
+        .and_then(|content| content.strip_suffix("</p>"))
+        .unwrap_or(content)
+        .to_owned()
+}
+
+fn collect_text(events: &[Event]) -> String {
+    let mut text = String::new();
+    for event in events {
+        match event {
+            Event::Text(value) | Event::Code(value) => text.push_str(value),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => (),
+        }
+    }
+    text
+}
+
+/// Give every heading an id, generated like pandoc's automatic identifiers
+/// when not set explicitly, and return the headings in document order.
+fn assign_heading_ids(events: &mut [Event]) -> Vec<Heading> {
+    let mut used_ids = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Start(Tag::Heading { id: Some(id), .. }) => Some(id.to_string()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    let mut headings = Vec::new();
+    for start in 0..events.len() {
+        let level = match &events[start] {
+            Event::Start(Tag::Heading { level, .. }) => *level,
+            _ => continue,
+        };
+        let end = events[start..]
+            .iter()
+            .position(|event| matches!(event, Event::End(TagEnd::Heading(_))))
+            .map(|end| start + end)
+            .unwrap_or(events.len());
+        let text = collect_text(&events[start + 1..end]);
+
+        if let Event::Start(Tag::Heading { id, .. }) = &mut events[start] {
+            if id.is_none() {
+                let base = auto_identifier(&text);
+                let mut unique = base.clone();
+                let mut suffix = 0;
+                while used_ids.contains(&unique) {
+                    suffix += 1;
+                    unique = format!("{}-{}", base, suffix);
+                }
+                used_ids.insert(unique.clone());
+                *id = Some(CowStr::from(unique));
+            }
+
+            headings.push(Heading {
+                level: level as usize,
+                id: id.as_deref().unwrap_or_default().to_owned(),
+                text,
+            });
+        }
+    }
+
+    headings
+}
+
+fn auto_identifier(text: &str) -> String {
+    let id = text
+        .chars()
+        .skip_while(|c| !c.is_alphabetic())
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                Some(c)
+            } else if c.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .flat_map(char::to_lowercase)
+        .collect::<String>();
+
+    if id.is_empty() {
+        "section".to_owned()
+    } else {
+        id
+    }
+}
+
+fn toc(headings: &[Heading]) -> Option<String> {
+    let mut toc = String::new();
+    let mut levels: Vec<usize> = Vec::new();
+
+    for heading in headings.iter().filter(|heading| heading.level <= TOC_DEPTH) {
+        match levels.last() {
+            Some(&last) if heading.level <= last => {
+                toc.push_str("</li>\n");
+                while levels.len() > 1 && heading.level <= levels[levels.len() - 2] {
+                    levels.pop();
+                    toc.push_str("</ul>\n</li>\n");
+                }
+            }
+            _ => {
+                toc.push_str("<ul>\n");
+                levels.push(heading.level);
+            }
+        }
+
+        toc.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>",
+            html_escape(&heading.id),
+            html_escape(&heading.text)
+        ));
+    }
+
+    if levels.is_empty() {
+        return None;
+    }
+
+    toc.push_str("</li>\n");
+    for _ in 1..levels.len() {
+        toc.push_str("</ul>\n</li>\n");
+    }
+    toc.push_str("</ul>\n");
+
+    Some(toc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_front_matter_lf() {
+        let (front_matter, body) =
+            split_front_matter("---\ntitle: Intro\nid: 'guide/intro'\n---\n\nHello\n");
+
+        assert_eq!(front_matter["title"], "Intro");
+        assert_eq!(front_matter["id"], "guide/intro");
+        assert_eq!(body, "\nHello\n");
+    }
+
+    #[test]
+    fn split_front_matter_crlf() {
+        let (front_matter, body) =
+            split_front_matter("---\r\ntitle: \"Intro\"\r\norder: 2\r\n---\r\n\r\nHello\r\n");
+
+        assert_eq!(front_matter["title"], "Intro");
+        assert_eq!(front_matter["order"], "2");
+        assert_eq!(body, "\r\nHello\r\n");
+    }
+
+    #[test]
+    fn split_front_matter_none() {
+        let source = "# Intro\n\n---\n";
+        assert_eq!(split_front_matter(source), (HashMap::new(), source));
+
+        let unterminated = "---\ntitle: Intro\n\nHello\n";
+        assert_eq!(
+            split_front_matter(unterminated),
+            (HashMap::new(), unterminated)
+        );
+    }
 }
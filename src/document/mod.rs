@@ -16,19 +16,28 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-mod asciidoc;
+mod jsondoc;
+#[cfg(feature = "markdown")]
 mod markdown;
-mod org;
 
-use crate::{site::SiteMetadata, Error};
+use crate::{
+    cache,
+    processor::{self, ProcessorConfig, ProcessorOutput},
+    site::SiteMetadata,
+    Error,
+};
+use handlebars::html_escape;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::{
-    fmt,
+    fmt, fs,
     path::{Component, Path, PathBuf},
     sync::Arc,
     time::SystemTime,
 };
 
-#[derive(Hash, Eq, Clone, PartialEq, Debug, PartialOrd, Ord)]
+#[derive(Hash, Eq, Clone, PartialEq, Debug, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DocumentName {
     pub id: Option<String>,
     pub labels: Vec<String>,
@@ -99,43 +108,44 @@ impl DocumentName {
             return self.post.is_none() && child.post.is_some();
         }
 
-        return true;
+        true
     }
 
     pub fn is_root(&self) -> bool {
         self.post.is_none() && self.labels.is_empty()
     }
+
+    /// Whether this is the site's `404` document, which is served for
+    /// missing paths instead of being part of the sitemap.
+    pub fn is_not_found(&self) -> bool {
+        self.post.is_none() && self.folder_path() == Path::new("404")
+    }
 }
 
-#[derive(Hash, Eq, Clone, PartialEq, Debug, PartialOrd, Ord)]
+#[derive(Hash, Eq, Clone, PartialEq, Debug, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DocumentPostLabel {
     pub date: String,
     pub label: String,
 }
 
-#[derive(Eq, Clone, PartialEq, Debug)]
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DocumentType {
     AsciiDoc,
     Markdown,
     Org,
-}
-
-impl<'a> TryFrom<&'a str> for DocumentType {
-    type Error = ();
-
-    fn try_from(s: &'a str) -> Result<DocumentType, ()> {
-        match s {
-            "adoc" => Ok(DocumentType::AsciiDoc),
-            "md" => Ok(DocumentType::Markdown),
-            "org" => Ok(DocumentType::Org),
-            _ => Err(()),
-        }
-    }
+    #[serde(skip)]
+    JsonDoc,
+    /// Any other format, rendered by the command of its processor.
+    #[serde(skip)]
+    Other,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug)]
 pub struct DocumentMetadata {
     pub typ: DocumentType,
+    pub processor: Option<ProcessorConfig>,
     pub modified: SystemTime,
     pub source_path: PathBuf,
     pub rel_source_path: PathBuf,
@@ -145,15 +155,54 @@ impl DocumentMetadata {
     pub fn new(
         site: &SiteMetadata,
         file_path: &Path,
-        typ: DocumentType,
+        extension: &str,
         modified: SystemTime,
-    ) -> Result<DocumentMetadata, Error> {
-        Ok(DocumentMetadata {
+    ) -> Result<Option<DocumentMetadata>, Error> {
+        let (typ, processor) = if let Some(processor) = site.processors.get(extension) {
+            let typ = processor.typ.clone().unwrap_or(DocumentType::Other);
+            (typ, Some(processor.clone()))
+        } else if extension == "jsondoc" {
+            (DocumentType::JsonDoc, None)
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(DocumentMetadata {
             source_path: file_path.to_owned(),
             rel_source_path: file_path.strip_prefix(&site.source_path)?.to_owned(),
             modified,
             typ,
-        })
+            processor,
+        }))
+    }
+
+    fn processor(&self) -> &ProcessorConfig {
+        self.processor
+            .as_ref()
+            .expect("document types other than jsondoc always have a processor; qed")
+    }
+
+    pub fn is_html(&self) -> bool {
+        match self.typ {
+            DocumentType::AsciiDoc
+            | DocumentType::Markdown
+            | DocumentType::Org
+            | DocumentType::Other => true,
+            DocumentType::JsonDoc => self::jsondoc::is_html(&self.rel_source_path),
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "markdown"), allow(unused_variables))]
+fn process_builtin(
+    typ: &DocumentType,
+    site_path: &Path,
+    rel_path: &Path,
+) -> Result<ProcessorOutput, Error> {
+    match typ {
+        #[cfg(feature = "markdown")]
+        DocumentType::Markdown => self::markdown::process_markdown(site_path, rel_path),
+        _ => Err(Error::NoBuiltinProcessor(typ.clone())),
     }
 }
 
@@ -166,7 +215,7 @@ fn derive_name(rel_file_path: &Path, id: Option<String>) -> Result<DocumentName,
         if let Component::Normal(component_name) = component {
             let component_name = component_name
                 .to_str()
-                .ok_or(Error::PathContainNonUnicode)?;
+                .ok_or_else(|| Error::PathContainNonUnicode(rel_file_path.to_owned()))?;
 
             if component_name == "_posts" {
                 is_post = true;
@@ -179,15 +228,15 @@ fn derive_name(rel_file_path: &Path, id: Option<String>) -> Result<DocumentName,
                 labels.push(component_name.to_owned());
             }
         } else {
-            return Err(Error::InvalidPathComponent);
+            return Err(Error::InvalidPathComponent(rel_file_path.to_owned()));
         }
     }
 
     let file_stem = rel_file_path
         .file_stem()
-        .ok_or(Error::InvalidPathComponent)?
+        .ok_or_else(|| Error::InvalidPathComponent(rel_file_path.to_owned()))?
         .to_str()
-        .ok_or(Error::PathContainNonUnicode)?;
+        .ok_or_else(|| Error::PathContainNonUnicode(rel_file_path.to_owned()))?;
     let post = if is_post {
         let file_parts = file_stem.split('-').collect::<Vec<_>>();
         let date_part = file_parts[0..3].join("-");
@@ -206,11 +255,13 @@ fn derive_name(rel_file_path: &Path, id: Option<String>) -> Result<DocumentName,
     Ok(DocumentName { id, labels, post })
 }
 
-#[derive(Eq, Clone, PartialEq, Debug)]
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct RenderedData {
     pub name: DocumentName,
     pub title: String,
     pub sitemap_title: Option<String>,
+    pub order: Option<usize>,
     pub content: String,
     pub toc: Option<String>,
     pub description: String,
@@ -218,6 +269,77 @@ pub struct RenderedData {
     pub license: Option<String>,
     pub license_code: Option<String>,
     pub specs: Vec<Spec>,
+    pub dependencies: Vec<Dependency>,
+    pub metadata: Map<String, Value>,
+}
+
+impl RenderedData {
+    /// Whether the document asked, with `noindex: true` in its metadata, to
+    /// be left out of `sitemap.xml`.
+    pub fn is_noindex(&self) -> bool {
+        self.metadata.get("noindex") == Some(&Value::Bool(true))
+    }
+
+    /// Whether none of the files the document was rendered from, other than
+    /// the document itself, changed since.
+    pub fn is_current(&self, site_path: &Path) -> bool {
+        self.dependencies
+            .iter()
+            .all(|dependency| dependency.is_current(site_path))
+    }
+}
+
+/// File that a document was rendered from, such as an AsciiDoc include, as
+/// reported by its processor.
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dependency {
+    pub path: PathBuf,
+    /// Modification time and digest of the file, both `None` if it could not
+    /// be read, such as a missing include.
+    pub modified: Option<SystemTime>,
+    pub digest: Option<String>,
+}
+
+impl Dependency {
+    pub fn new(site_path: &Path, path: PathBuf) -> Self {
+        let source_path = site_path.join(&path);
+        let modified = fs::metadata(&source_path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let digest = fs::read(&source_path)
+            .ok()
+            .map(|content| cache::digest(&content));
+
+        Self {
+            path,
+            modified,
+            digest,
+        }
+    }
+
+    pub fn is_missing(&self) -> bool {
+        self.digest.is_none()
+    }
+
+    /// Checks the modification time first, and only if that differs, for
+    /// example after a fresh checkout, the content. A missing dependency is
+    /// current until it appears.
+    pub fn is_current(&self, site_path: &Path) -> bool {
+        let source_path = site_path.join(&self.path);
+
+        match (
+            fs::metadata(&source_path).and_then(|metadata| metadata.modified()),
+            &self.digest,
+        ) {
+            (Ok(modified), Some(_)) if Some(modified) == self.modified => true,
+            (Ok(_), Some(digest)) => fs::read(&source_path)
+                .map(|content| cache::digest(&content) == *digest)
+                .unwrap_or(false),
+            (Ok(_), None) => false,
+            (Err(_), digest) => digest.is_none(),
+        }
+    }
 }
 
 #[derive(Eq, Clone, PartialEq, Debug)]
@@ -237,24 +359,47 @@ impl RenderedDocument {
         println!("[{}] Rendering document {:?} ...", site.name, rel_file_path);
 
         Ok(match document.typ {
-            DocumentType::AsciiDoc => {
-                let output = self::asciidoc::process_asciidoc(&site.source_path, &rel_file_path)?;
-                let id = None;
-                let name = derive_name(rel_file_path, id)?;
+            DocumentType::AsciiDoc
+            | DocumentType::Markdown
+            | DocumentType::Org
+            | DocumentType::Other => {
+                let output = if document.processor().command.is_some() {
+                    processor::process(document.processor(), &site, rel_file_path)?
+                } else {
+                    process_builtin(&document.typ, &site.source_path, rel_file_path)?
+                };
+                let name = derive_name(rel_file_path, output.id)?;
+                // Dependencies that cannot be read, such as a wrong include,
+                // are still tracked, so that the document is rendered again
+                // once they appear.
+                let dependencies = output
+                    .dependencies
+                    .into_iter()
+                    .map(|path| Dependency::new(&site.source_path, path))
+                    .collect::<Vec<_>>();
+                for dependency in dependencies.iter().filter(|d| d.is_missing()) {
+                    println!(
+                        "[{}] warning: {}: dependency {} does not exist",
+                        site.name,
+                        rel_file_path.display(),
+                        dependency.path.display()
+                    );
+                }
 
                 RenderedDocument {
                     site_metadata: site,
                     metadata: document,
                     data: Arc::new(RenderedData {
                         name,
-                        title: output.document.title,
-                        sitemap_title: None,
-                        content: output.document.content,
-                        toc: output.document.toc,
-                        description: output.document.description.clone(),
-                        description_content: None,
-                        license: output.document.license,
-                        license_code: output.document.license_code,
+                        title: output.title,
+                        sitemap_title: output.sitemap_title,
+                        order: output.order,
+                        content: output.content,
+                        toc: output.toc,
+                        description: output.description,
+                        description_content: output.description_content,
+                        license: output.license,
+                        license_code: output.license_code,
                         specs: output
                             .specs
                             .into_iter()
@@ -266,13 +411,25 @@ impl RenderedDocument {
                                 anchor: spec.anchor,
                             })
                             .collect(),
+                        dependencies,
+                        metadata: output.metadata,
                     }),
                 }
             }
-            DocumentType::Markdown => {
-                let output = self::markdown::process_markdown(&site.source_path, &rel_file_path)?;
+            DocumentType::JsonDoc => {
+                let output = self::jsondoc::process_jsondoc(&site.source_path, rel_file_path)?;
                 let id = output.id;
-                let name = derive_name(rel_file_path, id)?;
+                let is_html = document.is_html();
+                let name = if is_html {
+                    derive_name(&rel_file_path.with_extension(""), id)?
+                } else {
+                    derive_name(rel_file_path, id)?
+                };
+                let content = if is_html {
+                    output.content
+                } else {
+                    format!("<pre>{}</pre>", html_escape(&output.content))
+                };
 
                 RenderedDocument {
                     site_metadata: site,
@@ -281,35 +438,16 @@ impl RenderedDocument {
                         name,
                         title: output.title,
                         sitemap_title: output.sitemap_title,
-                        content: output.content,
-                        toc: Some(output.toc),
-                        description: output.description,
-                        description_content: Some(output.description_content),
-                        license: None,
-                        license_code: None,
-                        specs: Vec::new(),
-                    }),
-                }
-            }
-            DocumentType::Org => {
-                let output = self::org::process_org(&site.source_path, &rel_file_path)?;
-                let id = None;
-                let name = derive_name(rel_file_path, id)?;
-
-                RenderedDocument {
-                    site_metadata: site,
-                    metadata: document,
-                    data: Arc::new(RenderedData {
-                        name,
-                        title: output.title,
-                        sitemap_title: None,
-                        content: output.content,
-                        toc: Some(output.toc),
+                        order: output.order,
+                        content,
+                        toc: output.toc,
                         description: output.description,
-                        description_content: Some(output.description_content),
+                        description_content: output.description_content,
                         license: None,
                         license_code: None,
                         specs: Vec::new(),
+                        dependencies: Vec::new(),
+                        metadata: output.metadata,
                     }),
                 }
             }
@@ -317,7 +455,8 @@ impl RenderedDocument {
     }
 }
 
-#[derive(Eq, Clone, PartialEq, Debug)]
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Spec {
     pub id: String,
     pub description: String,
@@ -325,3 +464,33 @@ pub struct Spec {
     pub source_path: PathBuf,
     pub anchor: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn missing_dependency_is_current_until_created() {
+        let site_path = env::temp_dir().join(format!("eighty-dependency-{}", process::id()));
+        let _ = fs::remove_dir_all(&site_path);
+        fs::create_dir_all(site_path.join("_inc")).expect("temp dir is writable; qed");
+
+        let dependency = Dependency::new(&site_path, PathBuf::from("_inc/header.adoc"));
+        assert!(dependency.is_missing());
+        assert!(dependency.is_current(&site_path));
+
+        fs::write(site_path.join("_inc/header.adoc"), "= Header")
+            .expect("temp dir is writable; qed");
+        assert!(!dependency.is_current(&site_path));
+
+        let dependency = Dependency::new(&site_path, PathBuf::from("_inc/header.adoc"));
+        assert!(!dependency.is_missing());
+        assert!(dependency.is_current(&site_path));
+
+        fs::remove_file(site_path.join("_inc/header.adoc")).expect("temp dir is writable; qed");
+        assert!(!dependency.is_current(&site_path));
+
+        let _ = fs::remove_dir_all(&site_path);
+    }
+}
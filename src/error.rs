@@ -16,42 +16,236 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use std::fmt;
+use crate::{
+    document::{DocumentName, DocumentType},
+    site::SiteName,
+    xref::UnresolvedXref,
+};
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug)]
 pub enum Error {
-    PathContainNonUnicode,
-    InvalidPathComponent,
-    RunCommandFailed,
+    PathContainNonUnicode(PathBuf),
+    InvalidPathComponent(PathBuf),
+    SpawnCommandFailed {
+        command: String,
+        error: std::io::Error,
+    },
+    RunCommandFailed {
+        command: String,
+        status: Option<i32>,
+        stderr: String,
+    },
+    ProcessorFailed {
+        command: String,
+        message: String,
+        stderr: String,
+    },
     Io(std::io::Error),
     Json(serde_json::Error),
     StripPrefix(std::path::StripPrefixError),
     WalkDir(walkdir::Error),
-    ReservedSiteName,
+    ReservedSiteName(SiteName),
 
     TokioJoin(tokio::task::JoinError),
-    SiteNotExist,
-    DocumentNotFound,
+    SiteNotExist(SiteName),
+    DocumentNotFound(DocumentName),
     HyperHttp(hyper::http::Error),
-    HandlebarsTemplate(handlebars::TemplateError),
-    HandlebarsRender(handlebars::RenderError),
+    Bind {
+        address: SocketAddr,
+        error: hyper::Error,
+    },
+    HandlebarsTemplate(Box<handlebars::TemplateError>),
+    HandlebarsRender(Box<handlebars::RenderError>),
 
     Poisoned,
     Notify(notify::Error),
     Regex(regex::Error),
     UnprocessedRegexMatch,
-    UnsupportedVariable,
-    UnresolvedXreflink,
+    InvalidProcessorOutput(String),
+    NoBuiltinProcessor(DocumentType),
+    ProcessorWithoutCommand(String),
+    UnresolvedXrefs(Vec<UnresolvedXref>),
+
+    Site {
+        site: SiteName,
+        error: Box<Error>,
+    },
+    Path {
+        path: PathBuf,
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    fn write_summary(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::PathContainNonUnicode(path) => {
+                write!(
+                    f,
+                    "path contains non-unicode characters: {}",
+                    path.display()
+                )
+            }
+            Error::InvalidPathComponent(path) => {
+                write!(f, "invalid path component in {}", path.display())
+            }
+            Error::SpawnCommandFailed { command, error } => {
+                write!(f, "failed to run processor `{}`: {}", command, error)
+            }
+            Error::RunCommandFailed {
+                status: Some(code), ..
+            } => write!(f, "processor exited with code {}", code),
+            Error::RunCommandFailed { status: None, .. } => {
+                write!(f, "processor was terminated by a signal")
+            }
+            Error::ProcessorFailed { .. } => write!(f, "processor reported an error"),
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Json(err) => write!(f, "invalid JSON: {}", err),
+            Error::StripPrefix(err) => write!(f, "{}", err),
+            Error::WalkDir(err) => write!(f, "failed to walk directory: {}", err),
+            Error::ReservedSiteName(name) => write!(f, "site name {} is reserved", name),
+            Error::TokioJoin(err) => write!(f, "background task failed: {}", err),
+            Error::SiteNotExist(name) => write!(f, "site {} does not exist", name),
+            Error::DocumentNotFound(name) => write!(f, "document {} not found in sitemap", name),
+            Error::HyperHttp(err) => write!(f, "HTTP error: {}", err),
+            Error::Bind { address, error } => {
+                let in_use = std::error::Error::source(error)
+                    .and_then(|source| source.downcast_ref::<std::io::Error>())
+                    .map(|source| source.kind() == std::io::ErrorKind::AddrInUse)
+                    .unwrap_or(false);
+
+                if in_use {
+                    write!(
+                        f,
+                        "address {} is already in use, choose another with --port",
+                        address
+                    )
+                } else {
+                    write!(f, "failed to listen on {}: {}", address, error)
+                }
+            }
+            Error::HandlebarsTemplate(err) => write!(f, "template error: {}", err),
+            Error::HandlebarsRender(err) => write!(f, "template render error: {}", err),
+            Error::Poisoned => write!(f, "lock poisoned by a panicked thread"),
+            Error::Notify(err) => write!(f, "file watcher error: {}", err),
+            Error::Regex(err) => write!(f, "{}", err),
+            Error::UnprocessedRegexMatch => write!(f, "regex match without expected group"),
+            Error::InvalidProcessorOutput(reason) => {
+                write!(f, "invalid processor output: {}", reason)
+            }
+            Error::NoBuiltinProcessor(DocumentType::Markdown) => write!(
+                f,
+                "built-in markdown processor is not enabled in this build"
+            ),
+            Error::NoBuiltinProcessor(typ) => {
+                write!(f, "no built-in processor for {:?} documents", typ)
+            }
+            Error::ProcessorWithoutCommand(extension) => write!(
+                f,
+                "processor for .{} files needs a command, there is no built-in one",
+                extension
+            ),
+            Error::UnresolvedXrefs(xrefs) => {
+                write!(f, "{} unresolved cross-reference(s)", xrefs.len())
+            }
+            Error::Site { error, .. } | Error::Path { error, .. } => error.write_summary(f),
+        }
+    }
+
+    fn write_context(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Site { site, error } => {
+                write!(f, "\n  site: {}", site)?;
+                error.write_context(f)
+            }
+            Error::Path { path, error } => {
+                write!(f, "\n  path: {}", path.display())?;
+                error.write_context(f)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn write_details(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RunCommandFailed {
+                command, stderr, ..
+            } => {
+                write!(f, "\n  command: {}", command)?;
+                if !stderr.trim().is_empty() {
+                    write!(f, "\n  stderr:")?;
+                    for line in stderr.trim_end().lines() {
+                        write!(f, "\n    | {}", line)?;
+                    }
+                }
+                Ok(())
+            }
+            Error::ProcessorFailed {
+                command,
+                message,
+                stderr,
+            } => {
+                write!(f, "\n  command: {}", command)?;
+                for line in message.trim_end().lines() {
+                    write!(f, "\n    | {}", line)?;
+                }
+                if !stderr.trim().is_empty() {
+                    write!(f, "\n  stderr:")?;
+                    for line in stderr.trim_end().lines() {
+                        write!(f, "\n    | {}", line)?;
+                    }
+                }
+                Ok(())
+            }
+            Error::UnresolvedXrefs(xrefs) => {
+                for xref in xrefs {
+                    write!(f, "\n  {}", xref)?;
+                }
+                Ok(())
+            }
+            Error::Site { error, .. } | Error::Path { error, .. } => error.write_details(f),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        self.write_summary(f)?;
+        self.write_context(f)?;
+        self.write_details(f)
     }
 }
 
 impl std::error::Error for Error {}
 
+/// Attach the site or path a failure belongs to.
+pub trait ErrorContext<T> {
+    fn site_context(self, site: &SiteName) -> Result<T, Error>;
+    fn path_context(self, path: &Path) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> ErrorContext<T> for Result<T, E> {
+    fn site_context(self, site: &SiteName) -> Result<T, Error> {
+        self.map_err(|err| Error::Site {
+            site: site.clone(),
+            error: Box::new(err.into()),
+        })
+    }
+
+    fn path_context(self, path: &Path) -> Result<T, Error> {
+        self.map_err(|err| Error::Path {
+            path: path.to_owned(),
+            error: Box::new(err.into()),
+        })
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Error {
         Error::Io(err)
@@ -90,13 +284,13 @@ impl From<hyper::http::Error> for Error {
 
 impl From<handlebars::TemplateError> for Error {
     fn from(err: handlebars::TemplateError) -> Error {
-        Error::HandlebarsTemplate(err)
+        Error::HandlebarsTemplate(Box::new(err))
     }
 }
 
 impl From<handlebars::RenderError> for Error {
     fn from(err: handlebars::RenderError) -> Error {
-        Error::HandlebarsRender(err)
+        Error::HandlebarsRender(Box::new(err))
     }
 }
 
@@ -0,0 +1,357 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! Atom, RSS and JSON feeds of posts.
+
+use crate::{date, document::DocumentName, site::SiteConfig, sitemap::Sitemap};
+use handlebars::html_escape;
+use serde_json::json;
+use std::path::PathBuf;
+
+#[derive(Eq, Clone, Copy, PartialEq, Debug)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+    Json,
+}
+
+impl FeedFormat {
+    /// Formats generated for a site. Atom is always generated.
+    pub fn enabled(site: &SiteConfig) -> Vec<FeedFormat> {
+        let mut formats = vec![FeedFormat::Atom];
+        if site.feeds.rss {
+            formats.push(FeedFormat::Rss);
+        }
+        if site.feeds.json {
+            formats.push(FeedFormat::Json);
+        }
+        formats
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "feed.xml",
+            FeedFormat::Rss => "rss.xml",
+            FeedFormat::Json => "feed.json",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "application/atom+xml",
+            FeedFormat::Rss => "application/rss+xml",
+            FeedFormat::Json => "application/feed+json",
+        }
+    }
+}
+
+/// Feed of the posts in one `_posts` folder, or of all posts of a site.
+#[derive(Eq, Clone, PartialEq, Debug)]
+pub struct Feed {
+    /// Labels of the folder containing `_posts`, or `None` for the site feed.
+    pub folder: Option<Vec<String>>,
+    pub title: String,
+}
+
+impl Feed {
+    /// Path of the feed in `format`, relative to the site.
+    pub fn path(&self, format: FeedFormat) -> PathBuf {
+        let mut path = PathBuf::new();
+        for label in self.folder.iter().flatten() {
+            path.push(label);
+        }
+        path.push(format.file_name());
+        path
+    }
+
+    pub fn contains(&self, name: &DocumentName) -> bool {
+        name.post.is_some()
+            && self
+                .folder
+                .as_ref()
+                .map(|folder| *folder == name.labels)
+                .unwrap_or(true)
+    }
+}
+
+/// Feeds of a site: one of all posts, and one for each `_posts` folder below
+/// the site root. Sites without posts have none.
+pub fn feeds(site: &SiteConfig, sitemap: &Sitemap) -> Vec<Feed> {
+    let items = sitemap.walk();
+
+    let mut folders = items
+        .iter()
+        .filter(|item| item.document_name.post.is_some())
+        .map(|item| item.document_name.labels.clone())
+        .collect::<Vec<_>>();
+    if folders.is_empty() {
+        return Vec::new();
+    }
+    folders.sort();
+    folders.dedup();
+
+    let mut feeds = vec![Feed {
+        folder: None,
+        title: site.title.clone(),
+    }];
+    for folder in folders.into_iter().filter(|folder| !folder.is_empty()) {
+        let folder_title = items
+            .iter()
+            .find(|item| item.document_name.post.is_none() && item.document_name.labels == folder)
+            .map(|item| item.title.clone())
+            .unwrap_or_else(|| folder.join("/"));
+
+        feeds.push(Feed {
+            title: format!("{} - {}", folder_title, site.title),
+            folder: Some(folder),
+        });
+    }
+    feeds
+}
+
+/// Feed advertised on a page, for autodiscovery.
+#[derive(Eq, Clone, PartialEq, Debug)]
+pub struct FeedLink {
+    pub title: String,
+    pub url: String,
+    pub mime_type: String,
+}
+
+/// Feeds to advertise on a document: the site feed, and the feed of the
+/// `_posts` folder the document is a post of, or the index of.
+pub fn links(site: &SiteConfig, sitemap: &Sitemap, name: &DocumentName) -> Vec<FeedLink> {
+    let mut links = Vec::new();
+
+    for feed in feeds(site, sitemap) {
+        if feed
+            .folder
+            .as_ref()
+            .map(|folder| *folder != name.labels)
+            .unwrap_or(false)
+        {
+            continue;
+        }
+
+        for format in FeedFormat::enabled(site) {
+            links.push(FeedLink {
+                title: feed.title.clone(),
+                url: format!("{}{}", site.base_url, feed.path(format).display()),
+                mime_type: format.mime_type().to_string(),
+            });
+        }
+    }
+
+    links
+}
+
+/// Post in a feed.
+#[derive(Eq, Clone, PartialEq, Debug)]
+pub struct FeedItem {
+    pub title: String,
+    pub url: String,
+    /// Date of the post, as `2021-05-01`.
+    pub date: String,
+    pub summary: String,
+    /// Content with absolute URLs for links to other documents.
+    pub content: String,
+}
+
+/// Write a feed of `items`, which are expected newest first.
+pub fn render(format: FeedFormat, site: &SiteConfig, feed: &Feed, items: &[FeedItem]) -> String {
+    match format {
+        FeedFormat::Atom => atom(site, feed, items),
+        FeedFormat::Rss => rss(site, feed, items),
+        FeedFormat::Json => json_feed(site, feed, items),
+    }
+}
+
+fn atom(site: &SiteConfig, feed: &Feed, items: &[FeedItem]) -> String {
+    let feed_url = format!("{}{}", site.url, feed.path(FeedFormat::Atom).display());
+    let updated = items
+        .first()
+        .map(|item| datetime(&item.date))
+        .unwrap_or_else(|| datetime("1970-01-01"));
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+    );
+    xml.push_str(&format!("  <title>{}</title>\n", html_escape(&feed.title)));
+    xml.push_str(&format!(
+        "  <link href=\"{}\" rel=\"self\"/>\n",
+        html_escape(&feed_url)
+    ));
+    xml.push_str(&format!(
+        "  <link href=\"{}\"/>\n",
+        html_escape(&home_url(site, feed))
+    ));
+    xml.push_str(&format!("  <id>{}</id>\n", html_escape(&feed_url)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated));
+    xml.push_str(&format!(
+        "  <author>\n    <name>{}</name>\n",
+        html_escape(&site.author)
+    ));
+    if !site.email.is_empty() {
+        xml.push_str(&format!(
+            "    <email>{}</email>\n",
+            html_escape(&site.email)
+        ));
+    }
+    xml.push_str("  </author>\n");
+
+    for item in items {
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            html_escape(&item.title)
+        ));
+        xml.push_str(&format!(
+            "    <link href=\"{}\"/>\n",
+            html_escape(&item.url)
+        ));
+        xml.push_str(&format!("    <id>{}</id>\n", html_escape(&item.url)));
+        xml.push_str(&format!(
+            "    <published>{}</published>\n",
+            datetime(&item.date)
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            datetime(&item.date)
+        ));
+        if !item.summary.is_empty() {
+            xml.push_str(&format!(
+                "    <summary>{}</summary>\n",
+                html_escape(&item.summary)
+            ));
+        }
+        // Remaining relative links, such as images, are relative to the post.
+        xml.push_str(&format!(
+            "    <content type=\"html\" xml:base=\"{}\">{}</content>\n",
+            html_escape(&item.url),
+            html_escape(&item.content)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn rss(site: &SiteConfig, feed: &Feed, items: &[FeedItem]) -> String {
+    let feed_url = format!("{}{}", site.url, feed.path(FeedFormat::Rss).display());
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+         <channel>\n",
+    );
+    xml.push_str(&format!("  <title>{}</title>\n", html_escape(&feed.title)));
+    xml.push_str(&format!(
+        "  <link>{}</link>\n",
+        html_escape(&home_url(site, feed))
+    ));
+    xml.push_str(&format!(
+        "  <description>{}</description>\n",
+        html_escape(&feed.title)
+    ));
+    xml.push_str(&format!(
+        "  <atom:link href=\"{}\" rel=\"self\" type=\"{}\"/>\n",
+        html_escape(&feed_url),
+        FeedFormat::Rss.mime_type()
+    ));
+
+    for item in items {
+        xml.push_str("  <item>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            html_escape(&item.title)
+        ));
+        xml.push_str(&format!("    <link>{}</link>\n", html_escape(&item.url)));
+        xml.push_str(&format!("    <guid>{}</guid>\n", html_escape(&item.url)));
+        if let Some(date) = date::parse_date(&item.date) {
+            xml.push_str(&format!(
+                "    <pubDate>{}</pubDate>\n",
+                httpdate::fmt_http_date(date)
+            ));
+        }
+        // RSS authors must have an email address.
+        if site.email.is_empty() {
+            xml.push_str(&format!(
+                "    <dc:creator>{}</dc:creator>\n",
+                html_escape(&site.author)
+            ));
+        } else {
+            xml.push_str(&format!(
+                "    <author>{} ({})</author>\n",
+                html_escape(&site.email),
+                html_escape(&site.author)
+            ));
+        }
+        xml.push_str(&format!(
+            "    <description>{}</description>\n",
+            html_escape(&item.content)
+        ));
+        xml.push_str("  </item>\n");
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn json_feed(site: &SiteConfig, feed: &Feed, items: &[FeedItem]) -> String {
+    let feed_url = format!("{}{}", site.url, feed.path(FeedFormat::Json).display());
+
+    let value = json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": feed.title,
+        "home_page_url": home_url(site, feed),
+        "feed_url": feed_url,
+        "authors": [{ "name": site.author }],
+        "items": items
+            .iter()
+            .map(|item| {
+                let mut value = json!({
+                    "id": item.url,
+                    "url": item.url,
+                    "title": item.title,
+                    "content_html": item.content,
+                    "date_published": datetime(&item.date),
+                });
+                if !item.summary.is_empty() {
+                    value["summary"] = json!(item.summary);
+                }
+                value
+            })
+            .collect::<Vec<_>>(),
+    });
+
+    format!("{:#}\n", value)
+}
+
+/// Page the feed is about: the site root, or the index of the posts folder.
+fn home_url(site: &SiteConfig, feed: &Feed) -> String {
+    match &feed.folder {
+        Some(folder) => format!("{}{}/", site.url, folder.join("/")),
+        None => site.url.clone(),
+    }
+}
+
+fn datetime(date: &str) -> String {
+    format!("{}T00:00:00Z", date)
+}
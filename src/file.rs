@@ -25,5 +25,5 @@ pub struct FileMetadata {
     pub path: PathBuf,
     pub source_path: PathBuf,
     pub modified: SystemTime,
-    pub content: Vec<u8>,
+    pub content: Arc<Vec<u8>>,
 }
@@ -17,28 +17,28 @@
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
-    document::RenderedDocument,
-    sitemap::{LocalSitemap, Sitemap, SitemapItem},
+    document::{DocumentName, RenderedData, RenderedDocument},
+    feed,
+    site::SiteMetadata,
+    sitemap::{BreadcrumbItem, LocalSitemap, Sitemap, SitemapItem},
+    xref::XrefTarget,
     Error,
 };
 use handlebars::Handlebars;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct DocumentContext {
-    pub site_title: String,
-    pub site_title_only: bool,
-    pub site_base_url: String,
+    #[serde(flatten)]
+    pub site: SiteContext,
 
     pub page_title: String,
     pub page_description: Option<String>,
     pub page_description_content: Option<String>,
     pub page_url: String,
 
-    pub has_site_links: bool,
-    pub site_links: Vec<DocumentContextSiteLink>,
-
     pub toc: Option<String>,
     pub page_content: String,
 
@@ -48,6 +48,21 @@ struct DocumentContext {
 
     pub page_license: Option<String>,
     pub page_license_code: Option<String>,
+    pub page_metadata: Map<String, Value>,
+}
+
+/// Parts of the layout context shared by all pages of a site: its title,
+/// links, feeds and navigation.
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SiteContext {
+    pub site_title: String,
+    pub site_title_only: bool,
+    pub site_base_url: String,
+
+    pub has_site_links: bool,
+    pub site_links: Vec<DocumentContextSiteLink>,
+    pub feeds: Vec<DocumentContextFeed>,
 
     pub sitemap: Option<Vec<DocumentContextSitemapItem>>,
     pub local_sitemap: DocumentContextLocalSitemap,
@@ -55,14 +70,22 @@ struct DocumentContext {
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct DocumentContextSiteLink {
+pub(super) struct DocumentContextSiteLink {
     pub url: String,
     pub name: String,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct DocumentContextSitemapItem {
+pub(super) struct DocumentContextFeed {
+    pub title: String,
+    pub url: String,
+    pub mime_type: String,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct DocumentContextSitemapItem {
     pub title: String,
     pub url: String,
     pub children: Vec<DocumentContextSitemapItem>,
@@ -70,19 +93,37 @@ struct DocumentContextSitemapItem {
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct DocumentContextLocalSitemap {
+pub(super) struct DocumentContextLocalSitemap {
     pub breadcrumb: Option<Vec<DocumentContextBreadcrumbItem>>,
     pub children: Option<Vec<DocumentContextBreadcrumbItem>>,
+    pub previous: Option<DocumentContextBreadcrumbItem>,
+    pub next: Option<DocumentContextBreadcrumbItem>,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct DocumentContextBreadcrumbItem {
+pub(super) struct DocumentContextBreadcrumbItem {
     pub title: String,
     pub url: String,
     pub description: String,
 }
 
+impl DocumentContextBreadcrumbItem {
+    pub fn from_breadcrumb_item(item: &BreadcrumbItem, site_base_url: &str) -> Self {
+        Self {
+            title: item.title.clone(),
+            // The site root is the previous document of the first one when
+            // reading the whole site.
+            url: format!(
+                "{}{}",
+                site_base_url,
+                XrefTarget::Document(item.document_name.clone()).link()
+            ),
+            description: item.description.clone(),
+        }
+    }
+}
+
 impl DocumentContextSitemapItem {
     pub fn from_sitemap_item(item: SitemapItem, max_depth: Option<usize>, site_base_url: &str) -> Self {
         let show_children = max_depth.map(|d| d > 0).unwrap_or(true);
@@ -108,114 +149,187 @@ impl DocumentContextSitemapItem {
     }
 }
 
+impl SiteContext {
+    /// Context of a page of the site with navigation `local_sitemap`, and
+    /// the feeds relevant to `name`.
+    pub(super) fn new(
+        site_metadata: &SiteMetadata,
+        sitemap: &Sitemap,
+        local_sitemap: &LocalSitemap,
+        name: &DocumentName,
+    ) -> Self {
+        let site_config = &site_metadata.config;
+
+        Self {
+            site_title: site_config.title.clone(),
+            site_title_only: false,
+            site_base_url: site_config.base_url.clone(),
+
+            has_site_links: false,
+            site_links: site_config
+                .links
+                .iter()
+                .map(|link| DocumentContextSiteLink {
+                    url: link.url.clone(),
+                    name: link.name.clone(),
+                })
+                .collect(),
+            feeds: feed::links(site_config, sitemap, name)
+                .into_iter()
+                .map(|link| DocumentContextFeed {
+                    title: link.title,
+                    url: link.url,
+                    mime_type: link.mime_type,
+                })
+                .collect(),
+
+            sitemap: if site_config.sitemap.enable {
+                Some(
+                    sitemap
+                        .iter()
+                        .map(|child| {
+                            DocumentContextSitemapItem::from_sitemap_item(
+                                child.clone(),
+                                site_config.sitemap.depth.map(|d| d.saturating_sub(1)),
+                                &site_config.base_url,
+                            )
+                        })
+                        .chain(site_config.sitemap.extra_links.iter().map(|link| {
+                            DocumentContextSitemapItem {
+                                title: link.title.clone(),
+                                url: link.url.clone(),
+                                children: Vec::new(),
+                            }
+                        }))
+                        .collect(),
+                )
+            } else {
+                None
+            },
+            local_sitemap: DocumentContextLocalSitemap {
+                breadcrumb: if !local_sitemap.breadcrumb.is_empty() {
+                    Some(
+                        local_sitemap
+                            .breadcrumb
+                            .iter()
+                            .map(|item| {
+                                DocumentContextBreadcrumbItem::from_breadcrumb_item(
+                                    item,
+                                    &site_config.base_url,
+                                )
+                            })
+                            .collect(),
+                    )
+                } else {
+                    None
+                },
+                children: if !local_sitemap.children.is_empty() {
+                    Some(
+                        local_sitemap
+                            .children
+                            .iter()
+                            .map(|item| {
+                                DocumentContextBreadcrumbItem::from_breadcrumb_item(
+                                    item,
+                                    &site_config.base_url,
+                                )
+                            })
+                            .collect(),
+                    )
+                } else {
+                    None
+                },
+                previous: local_sitemap.previous.as_ref().map(|item| {
+                    DocumentContextBreadcrumbItem::from_breadcrumb_item(item, &site_config.base_url)
+                }),
+                next: local_sitemap.next.as_ref().map(|item| {
+                    DocumentContextBreadcrumbItem::from_breadcrumb_item(item, &site_config.base_url)
+                }),
+            },
+        }
+    }
+}
+
 pub fn layout(
     rendered: &RenderedDocument,
     sitemap: &Sitemap,
     local_sitemap: &LocalSitemap,
     handlebars: &Handlebars,
 ) -> Result<String, Error> {
-    let site_config = &rendered.site_metadata.config;
+    let page_url = format!(
+        "{}{}",
+        rendered.site_metadata.config.url,
+        rendered.data.name.folder_path().display()
+    );
+
+    render(
+        &rendered.site_metadata,
+        &rendered.data,
+        sitemap,
+        local_sitemap,
+        page_url,
+        "document/main",
+        handlebars,
+    )
+}
+
+/// Lay out the page served for missing paths, with the `document/404`
+/// layout if there is one.
+pub fn not_found_layout(
+    site_metadata: &SiteMetadata,
+    data: &RenderedData,
+    sitemap: &Sitemap,
+    local_sitemap: &LocalSitemap,
+    handlebars: &Handlebars,
+) -> Result<String, Error> {
+    let page_url = format!("{}404.html", site_metadata.config.url);
+    let template = if handlebars.has_template("document/404") {
+        "document/404"
+    } else {
+        "document/main"
+    };
 
+    render(
+        site_metadata,
+        data,
+        sitemap,
+        local_sitemap,
+        page_url,
+        template,
+        handlebars,
+    )
+}
+
+fn render(
+    site_metadata: &SiteMetadata,
+    data: &RenderedData,
+    sitemap: &Sitemap,
+    local_sitemap: &LocalSitemap,
+    page_url: String,
+    template: &str,
+    handlebars: &Handlebars,
+) -> Result<String, Error> {
     let context = DocumentContext {
-        site_title: site_config.title.clone(),
-        site_title_only: false,
-        site_base_url: site_config.base_url.clone(),
-
-        page_title: rendered.data.title.clone(),
-        page_description: Some(rendered.data.description.clone()),
-        page_description_content: rendered.data.description_content.clone(),
-        page_url: format!(
-            "{}{}",
-            rendered.site_metadata.config.url,
-            rendered.data.name.folder_path().display()
-        ),
-
-        has_site_links: false,
-        site_links: site_config
-            .links
-            .iter()
-            .map(|link| DocumentContextSiteLink {
-                url: link.url.clone(),
-                name: link.name.clone(),
-            })
-            .collect(),
-
-        toc: rendered.data.toc.clone(),
-        page_content: rendered.data.content.clone(),
+        site: SiteContext::new(site_metadata, sitemap, local_sitemap, &data.name),
+
+        page_title: data.title.clone(),
+        page_description: Some(data.description.clone()),
+        page_description_content: data.description_content.clone(),
+        page_url,
+
+        toc: data.toc.clone(),
+        page_content: data.content.clone(),
 
         page_author_url: "https://social.pacna.org/@wei".to_string(),
         page_author: "Wei Tang".to_string(),
         page_copyright_years: "2019-2023".to_string(),
 
-        page_license: rendered.data.license.clone(),
-        page_license_code: rendered.data.license_code.clone(),
-
-        sitemap: if site_config.sitemap.enable {
-            Some(
-                sitemap
-                    .iter()
-                    .map(|child| {
-                        DocumentContextSitemapItem::from_sitemap_item(
-                            child.clone(),
-                            site_config.sitemap.depth.map(|d| d.saturating_sub(1)),
-                            &site_config.base_url,
-                        )
-                    })
-                    .chain(site_config.sitemap.extra_links.iter().map(|link| {
-                        DocumentContextSitemapItem {
-                            title: link.title.clone(),
-                            url: link.url.clone(),
-                            children: Vec::new(),
-                        }
-                    }))
-                    .collect(),
-            )
-        } else {
-            None
-        },
-        local_sitemap: DocumentContextLocalSitemap {
-            breadcrumb: if local_sitemap.breadcrumb.len() > 0 {
-                Some(
-                    local_sitemap
-                        .breadcrumb
-                        .iter()
-                        .map(|item| DocumentContextBreadcrumbItem {
-                            title: item.title.clone(),
-                            url: format!(
-                                "{}{}/",
-                                site_config.base_url,
-                                item.document_name.folder_path().display()
-                            ),
-                            description: item.description.clone(),
-                        })
-                        .collect(),
-                )
-            } else {
-                None
-            },
-            children: if local_sitemap.children.len() > 0 {
-                Some(
-                    local_sitemap
-                        .children
-                        .iter()
-                        .map(|item| DocumentContextBreadcrumbItem {
-                            title: item.title.clone(),
-                            url: format!(
-                                "{}{}/",
-                                site_config.base_url,
-                                item.document_name.folder_path().display()
-                            ),
-                            description: item.description.clone(),
-                        })
-                        .collect(),
-                )
-            } else {
-                None
-            },
-        },
+        page_license: data.license.clone(),
+        page_license_code: data.license_code.clone(),
+        page_metadata: data.metadata.clone(),
     };
 
-    let layouted = handlebars.render("document/main", &context)?;
+    let layouted = handlebars.render(template, &context)?;
 
     Ok(layouted)
 }
@@ -17,9 +17,14 @@
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
 mod document;
+mod posts;
 mod spec;
 
 pub use self::{
-    document::layout as document,
+    document::{layout as document, not_found_layout as not_found},
+    posts::{
+        list_page_path, posts_layout as posts, ArchiveMonth, ArchiveYear, PostsPage, PostsPageItem,
+        PostsPageKind,
+    },
     spec::{index_layout as spec_index, redirect_layout as spec_redirect},
 };
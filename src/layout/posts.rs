@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use super::document::SiteContext;
+use crate::{
+    document::{DocumentName, RenderedData},
+    site::SiteMetadata,
+    sitemap::{LocalSitemap, Sitemap},
+    Error,
+};
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+/// Generated page listing the posts of one `_posts` folder.
+pub struct PostsPage<'a> {
+    /// Title of the posts folder, from its index document.
+    pub folder_title: String,
+    /// Name of the posts folder, as if it had an index document.
+    pub folder_name: DocumentName,
+    /// Path of this page, relative to the site.
+    pub path: PathBuf,
+    pub posts: Vec<PostsPageItem<'a>>,
+    pub kind: PostsPageKind,
+    pub archives: &'a [ArchiveYear],
+}
+
+pub struct PostsPageItem<'a> {
+    pub data: &'a RenderedData,
+    /// Content of the post with links resolved.
+    pub content: &'a str,
+}
+
+pub enum PostsPageKind {
+    /// Page of the listing of all posts, starting from 1.
+    List { page: usize, pages: usize },
+    /// Posts of a year, or of a month of it.
+    Archive { year: String, month: Option<String> },
+}
+
+/// Year with posts in a folder, newest first, for archive navigation.
+pub struct ArchiveYear {
+    pub year: String,
+    pub count: usize,
+    pub months: Vec<ArchiveMonth>,
+}
+
+pub struct ArchiveMonth {
+    pub month: String,
+    pub count: usize,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostsContext {
+    #[serde(flatten)]
+    pub site: SiteContext,
+
+    pub page_title: String,
+    pub page_url: String,
+
+    pub folder_title: String,
+    pub folder_url: String,
+
+    pub posts: Vec<PostsContextItem>,
+    pub pagination: Option<PostsContextPagination>,
+    pub archive: Option<PostsContextArchive>,
+    pub archives: Vec<PostsContextArchiveYear>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostsContextItem {
+    pub title: String,
+    pub url: String,
+    pub date: String,
+    pub year: String,
+    pub month: String,
+    pub day: String,
+    pub label: String,
+    pub description: String,
+    pub description_content: Option<String>,
+    pub content: String,
+    pub license: Option<String>,
+    pub license_code: Option<String>,
+    pub metadata: Map<String, Value>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostsContextPagination {
+    pub page: usize,
+    pub pages: usize,
+    pub previous_url: Option<String>,
+    pub next_url: Option<String>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostsContextArchive {
+    pub year: String,
+    pub month: Option<String>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostsContextArchiveYear {
+    pub year: String,
+    pub url: String,
+    pub count: usize,
+    pub months: Vec<PostsContextArchiveMonth>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostsContextArchiveMonth {
+    pub month: String,
+    pub url: String,
+    pub count: usize,
+}
+
+/// Path of a page of a posts listing, relative to the site.
+pub fn list_page_path(folder_path: &Path, page: usize) -> PathBuf {
+    folder_path.join("page").join(page.to_string())
+}
+
+fn url(base_url: &str, path: &Path) -> String {
+    if path.as_os_str().is_empty() {
+        base_url.to_owned()
+    } else {
+        format!("{}{}/", base_url, path.display())
+    }
+}
+
+/// Lay out a posts listing page with the `posts/list` layout, or an archive
+/// page with `posts/archive`, with the navigation of the posts folder.
+pub fn posts_layout(
+    site_metadata: &SiteMetadata,
+    sitemap: &Sitemap,
+    local_sitemap: &LocalSitemap,
+    page: &PostsPage,
+    handlebars: &Handlebars,
+) -> Result<String, Error> {
+    let site_config = &site_metadata.config;
+    let base_url = &site_config.base_url;
+    let folder_path = page.folder_name.folder_path();
+
+    let (template, page_title, pagination, archive) = match &page.kind {
+        PostsPageKind::List {
+            page: number,
+            pages,
+        } => (
+            "posts/list",
+            page.folder_title.clone(),
+            Some(PostsContextPagination {
+                page: *number,
+                pages: *pages,
+                previous_url: (*number > 1)
+                    .then(|| url(base_url, &list_page_path(&folder_path, number - 1))),
+                next_url: (number < pages)
+                    .then(|| url(base_url, &list_page_path(&folder_path, number + 1))),
+            }),
+            None,
+        ),
+        PostsPageKind::Archive { year, month } => (
+            "posts/archive",
+            match month {
+                Some(month) => format!("{}: {}-{}", page.folder_title, year, month),
+                None => format!("{}: {}", page.folder_title, year),
+            },
+            None,
+            Some(PostsContextArchive {
+                year: year.clone(),
+                month: month.clone(),
+            }),
+        ),
+    };
+
+    let context = PostsContext {
+        site: SiteContext::new(site_metadata, sitemap, local_sitemap, &page.folder_name),
+
+        page_title,
+        page_url: url(&site_config.url, &page.path),
+
+        folder_title: page.folder_title.clone(),
+        folder_url: url(base_url, &folder_path),
+
+        posts: page
+            .posts
+            .iter()
+            .map(|item| {
+                let post = item.data.name.post.as_ref();
+                let date = post.map(|post| post.date.clone()).unwrap_or_default();
+                let mut date_parts = date.split('-').map(|part| part.to_owned());
+
+                PostsContextItem {
+                    title: item.data.title.clone(),
+                    url: url(base_url, &item.data.name.folder_path()),
+                    year: date_parts.next().unwrap_or_default(),
+                    month: date_parts.next().unwrap_or_default(),
+                    day: date_parts.next().unwrap_or_default(),
+                    date,
+                    label: post.map(|post| post.label.clone()).unwrap_or_default(),
+                    description: item.data.description.clone(),
+                    description_content: item.data.description_content.clone(),
+                    content: item.content.to_owned(),
+                    license: item.data.license.clone(),
+                    license_code: item.data.license_code.clone(),
+                    metadata: item.data.metadata.clone(),
+                }
+            })
+            .collect(),
+        pagination,
+        archive,
+        archives: page
+            .archives
+            .iter()
+            .map(|year| {
+                let year_path = folder_path.join(&year.year);
+
+                PostsContextArchiveYear {
+                    year: year.year.clone(),
+                    url: url(base_url, &year_path),
+                    count: year.count,
+                    months: year
+                        .months
+                        .iter()
+                        .map(|month| PostsContextArchiveMonth {
+                            month: month.month.clone(),
+                            url: url(base_url, &year_path.join(&month.month)),
+                            count: month.count,
+                        })
+                        .collect(),
+                }
+            })
+            .collect(),
+    };
+
+    let layouted = handlebars.render(template, &context)?;
+
+    Ok(layouted)
+}
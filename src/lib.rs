@@ -17,13 +17,20 @@
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
 pub mod asset;
+pub mod cache;
+pub mod crawler;
+pub mod date;
 pub mod document;
 mod error;
+pub mod feed;
 pub mod file;
 pub mod layout;
+pub mod link;
+pub mod processor;
 pub mod site;
 pub mod sitemap;
 pub mod variable;
 pub mod workspace;
+pub mod xref;
 
-pub use crate::error::Error;
+pub use crate::error::{Error, ErrorContext};
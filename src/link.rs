@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::Error;
+use regex::Regex;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Link {
+    pub full: String,
+    pub target: String,
+}
+
+impl Link {
+    /// Resolve a relative link target against the source path of the document
+    /// containing it. Returns the site-relative path and an optional fragment,
+    /// or `None` if the link is absolute, external or escapes the site.
+    pub fn resolve(&self, rel_source_path: &Path) -> Option<(PathBuf, Option<String>)> {
+        let target = self.target.as_str();
+
+        if target.is_empty() || target.starts_with('#') || target.starts_with('/') {
+            return None;
+        }
+
+        let scheme_end = target.find(':');
+        let path_end = target.find(['/', '?', '#']);
+        if let Some(scheme_end) = scheme_end {
+            if path_end
+                .map(|path_end| scheme_end < path_end)
+                .unwrap_or(true)
+            {
+                return None;
+            }
+        }
+
+        let mut splited = target.splitn(2, '#');
+        let path_part = splited.next().expect("will return at least one item; qed");
+        let fragment = splited.next().map(|v| v.to_string());
+        let path_part = path_part
+            .split('?')
+            .next()
+            .expect("will return at least one item; qed");
+
+        let mut resolved = PathBuf::new();
+        let base = rel_source_path.parent().unwrap_or_else(|| Path::new(""));
+        for component in base.join(path_part).components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::CurDir => (),
+                Component::ParentDir => {
+                    if !resolved.pop() {
+                        return None;
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => return None,
+            }
+        }
+
+        Some((resolved, fragment))
+    }
+}
+
+pub fn search(content: &str) -> Result<Vec<Link>, Error> {
+    let mut matches = Vec::new();
+
+    let re = Regex::new(r#"href="([^"]*)""#)?;
+
+    for caps in re.captures_iter(content) {
+        let full = caps
+            .get(0)
+            .ok_or(Error::UnprocessedRegexMatch)?
+            .as_str()
+            .to_owned();
+        let target = caps
+            .get(1)
+            .ok_or(Error::UnprocessedRegexMatch)?
+            .as_str()
+            .to_owned();
+        matches.push(Link { full, target });
+    }
+
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(target: &str) -> Option<(PathBuf, Option<String>)> {
+        Link {
+            full: format!("href=\"{}\"", target),
+            target: target.to_owned(),
+        }
+        .resolve(Path::new("guide/intro.md"))
+    }
+
+    #[test]
+    fn resolve_relative_to_document() {
+        assert_eq!(resolve("setup.md"), Some(("guide/setup.md".into(), None)));
+        assert_eq!(
+            resolve("../other/./page.md"),
+            Some(("other/page.md".into(), None))
+        );
+    }
+
+    #[test]
+    fn resolve_fragment_and_query() {
+        assert_eq!(
+            resolve("setup.md?plain=1#install"),
+            Some(("guide/setup.md".into(), Some("install".to_owned())))
+        );
+        assert_eq!(
+            resolve("dir/a:b.md"),
+            Some(("guide/dir/a:b.md".into(), None))
+        );
+    }
+
+    #[test]
+    fn resolve_skips_absolute_and_external() {
+        assert_eq!(resolve(""), None);
+        assert_eq!(resolve("#install"), None);
+        assert_eq!(resolve("/guide/setup.md"), None);
+        assert_eq!(resolve("https://example.com/setup.md"), None);
+        assert_eq!(resolve("mailto:someone@example.com"), None);
+        assert_eq!(resolve("../../setup.md"), None);
+    }
+
+    #[test]
+    fn search_finds_href_attributes() {
+        let links =
+            search(r#"<a href="a.md">A</a> <link href="b.css">"#).expect("regex is valid; qed");
+        assert_eq!(
+            links
+                .into_iter()
+                .map(|link| link.target)
+                .collect::<Vec<_>>(),
+            vec!["a.md", "b.css"]
+        );
+    }
+}
@@ -19,20 +19,64 @@
 mod command;
 
 use clap::{App, Arg, SubCommand};
-use eighty::Error;
-use std::path::Path;
+use eighty::{site::SiteName, Error};
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    process,
+};
 
-fn main() -> Result<(), Error> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
     let matches = App::new("Eighty")
         .subcommand(
             SubCommand::with_name("build")
                 .arg(Arg::with_name("root").index(1).required(true))
-                .arg(Arg::with_name("target").index(2).required(true)),
+                .arg(Arg::with_name("target").index(2).required(true))
+                .arg(allow_unresolved_xrefs_arg()),
         )
         .subcommand(
             SubCommand::with_name("serve")
                 .arg(Arg::with_name("root").index(1).required(true))
-                .arg(Arg::with_name("site").index(2).required(true)),
+                .arg(
+                    Arg::with_name("site")
+                        .index(2)
+                        .help("Serve only this site, instead of all sites"),
+                )
+                .arg(
+                    Arg::with_name("address")
+                        .long("address")
+                        .takes_value(true)
+                        .default_value("127.0.0.1")
+                        .validator(|v| v.parse::<IpAddr>().map(|_| ()).map_err(|e| e.to_string()))
+                        .help("Address to listen on"),
+                )
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .takes_value(true)
+                        .default_value("8000")
+                        .validator(|v| v.parse::<u16>().map(|_| ()).map_err(|e| e.to_string()))
+                        .help("Port to listen on"),
+                )
+                .arg(
+                    Arg::with_name("local-urls")
+                        .long("local-urls")
+                        .help("Point site urls at the server, the default without a site"),
+                )
+                .arg(
+                    Arg::with_name("no-local-urls")
+                        .long("no-local-urls")
+                        .conflicts_with("local-urls")
+                        .help("Keep site urls when serving all sites, for routing by host"),
+                )
+                .arg(allow_unresolved_xrefs_arg()),
         )
         .get_matches();
 
@@ -40,13 +84,45 @@ fn main() -> Result<(), Error> {
         let root_path = Path::new(matches.value_of("root").expect("root is required"));
         let target_path = Path::new(matches.value_of("target").expect("target is required"));
 
-        command::build::build(&root_path, &target_path)?;
+        let allow_unresolved_xrefs = matches.is_present("allow-unresolved-xrefs");
+
+        command::build::build(root_path, target_path, allow_unresolved_xrefs)?;
     } else if let Some(matches) = matches.subcommand_matches("serve") {
         let root_path = Path::new(matches.value_of("root").expect("root is required"));
-        let site_name = matches.value_of("site").expect("site is required");
+        let site_name = matches
+            .value_of("site")
+            .map(|name| SiteName(name.to_string()));
+        let address = matches
+            .value_of("address")
+            .expect("address has a default")
+            .parse::<IpAddr>()
+            .expect("address is validated; qed");
+        let port = matches
+            .value_of("port")
+            .expect("port has a default")
+            .parse::<u16>()
+            .expect("port is validated; qed");
 
-        command::serve::serve(&root_path, &site_name)?;
+        // All sites are served under their own path segment, which links
+        // built from the site urls would leave out.
+        let local_urls = matches.is_present("local-urls")
+            || (site_name.is_none() && !matches.is_present("no-local-urls"));
+
+        let options = command::serve::Options {
+            site_name,
+            address: SocketAddr::new(address, port),
+            local_urls,
+            allow_unresolved_xrefs: matches.is_present("allow-unresolved-xrefs"),
+        };
+
+        command::serve::serve(root_path, options)?;
     }
 
     Ok(())
 }
+
+fn allow_unresolved_xrefs_arg() -> Arg<'static, 'static> {
+    Arg::with_name("allow-unresolved-xrefs")
+        .long("allow-unresolved-xrefs")
+        .help("Report unresolved cross-references as warnings instead of failing")
+}
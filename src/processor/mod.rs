@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+mod worker;
+
+use crate::{document::DocumentType, site::SiteMetadata, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::UNIX_EPOCH,
+};
+
+/// Version of the processor output schema understood by this build.
+pub const OUTPUT_VERSION: u32 = 1;
+
+/// External processor for one file extension. The command is run from within
+/// the site folder with `args` followed by the document path, and must print
+/// a `ProcessorOutput` as JSON. Without a command, the built-in renderer of
+/// the document type is used. Processors with `server` set are instead kept
+/// running with `--server`, see the `worker` module.
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessorConfig {
+    /// Source format, only needed to pick a built-in renderer.
+    #[serde(rename = "type", default)]
+    pub typ: Option<DocumentType>,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub server: bool,
+}
+
+impl ProcessorConfig {
+    fn new(typ: DocumentType, command: &str) -> Self {
+        Self {
+            typ: Some(typ),
+            command: Some(command.to_owned()),
+            args: Vec::new(),
+            env: HashMap::new(),
+            server: true,
+        }
+    }
+
+    /// Path, size and modification time of the executable, so that upgrading
+    /// a processor invalidates what it rendered before.
+    pub fn executable_stamp(&self, site_path: &Path) -> Option<String> {
+        let command = self.command.as_deref()?;
+        let path = if command.contains('/') {
+            Some(site_path.join(command))
+        } else {
+            env::var_os("PATH").and_then(|paths| {
+                env::split_paths(&paths)
+                    .map(|path| path.join(command))
+                    .find(|path| path.is_file())
+            })
+        }?;
+        let metadata = fs::metadata(&path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        Some(format!(
+            "{}:{}:{}",
+            path.display(),
+            metadata.len(),
+            modified.as_nanos()
+        ))
+    }
+
+    /// Processors without a command use the built-in renderer of their type,
+    /// which is only available for the type's own extension.
+    pub fn validate(&self, extension: &str) -> Result<(), Error> {
+        if self.command.is_some() {
+            return Ok(());
+        }
+
+        let builtin_typ = match extension {
+            "adoc" => Some(DocumentType::AsciiDoc),
+            "md" => Some(DocumentType::Markdown),
+            "org" => Some(DocumentType::Org),
+            _ => None,
+        };
+        if builtin_typ.is_none() || builtin_typ != self.typ {
+            return Err(Error::ProcessorWithoutCommand(extension.to_owned()));
+        }
+
+        Ok(())
+    }
+
+    fn command(&self) -> &str {
+        self.command
+            .as_deref()
+            .expect("built-in processors are never run as commands; qed")
+    }
+}
+
+pub fn default_processors() -> HashMap<String, ProcessorConfig> {
+    let mut processors = HashMap::new();
+    processors.insert(
+        "adoc".to_owned(),
+        ProcessorConfig::new(DocumentType::AsciiDoc, "eighty-asciidoc"),
+    );
+    processors.insert(
+        "md".to_owned(),
+        ProcessorConfig::new(DocumentType::Markdown, "eighty-pandoc"),
+    );
+    processors.insert(
+        "org".to_owned(),
+        ProcessorConfig::new(DocumentType::Org, "eighty-pandoc"),
+    );
+    processors
+}
+
+/// Run a document processor on `rel_path` from within the site folder and
+/// return its standard output. Anything the processor wrote to standard error
+/// on a successful run is forwarded to the build log as warnings.
+pub fn run(
+    processor: &ProcessorConfig,
+    site: &SiteMetadata,
+    rel_path: &Path,
+) -> Result<Vec<u8>, Error> {
+    let mut command = Command::new(processor.command());
+    command
+        .args(&processor.args)
+        .arg(rel_path)
+        .envs(&processor.env)
+        .current_dir(&site.source_path);
+
+    let command_line = command_line(&command);
+    let output = command
+        .output()
+        .map_err(|error| Error::SpawnCommandFailed {
+            command: command_line.clone(),
+            error,
+        })?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    if !output.status.success() {
+        return Err(Error::RunCommandFailed {
+            command: command_line,
+            status: output.status.code(),
+            stderr: stderr.into_owned(),
+        });
+    }
+
+    print_warnings(site, rel_path, stderr.lines());
+
+    Ok(output.stdout)
+}
+
+fn print_warnings<'a>(
+    site: &SiteMetadata,
+    rel_path: &Path,
+    warnings: impl IntoIterator<Item = &'a str>,
+) {
+    for line in warnings.into_iter().filter(|line| !line.trim().is_empty()) {
+        println!("[{}] warning: {}: {}", site.name, rel_path.display(), line);
+    }
+}
+
+/// Document rendered by an external processor. The schema is documented in
+/// the "Processor output" section of the README.
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProcessorOutput {
+    pub version: u32,
+    pub id: Option<String>,
+    pub title: String,
+    pub sitemap_title: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    pub description_content: Option<String>,
+    pub order: Option<usize>,
+    pub toc: Option<String>,
+    pub content: String,
+    pub license: Option<String>,
+    pub license_code: Option<String>,
+    #[serde(default)]
+    pub specs: Vec<ProcessorOutputSpec>,
+    #[serde(default)]
+    pub dependencies: Vec<PathBuf>,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProcessorOutputSpec {
+    pub id: String,
+    pub description: String,
+    pub discuss: String,
+    pub source_path: String,
+    pub anchor: String,
+}
+
+impl ProcessorOutput {
+    pub fn parse(stdout: &[u8]) -> Result<Self, Error> {
+        Self::from_value(serde_json::from_slice(stdout)?)
+    }
+
+    /// Validate processor output. The version is checked first, so that
+    /// output of a newer schema is reported as such rather than as unknown
+    /// fields.
+    pub fn from_value(value: Value) -> Result<Self, Error> {
+        match value.get("version").map(Value::as_u64) {
+            Some(Some(version)) if version == u64::from(OUTPUT_VERSION) => (),
+            Some(Some(version)) => {
+                return Err(Error::InvalidProcessorOutput(format!(
+                    "unsupported version {}, expected {}",
+                    version, OUTPUT_VERSION
+                )))
+            }
+            Some(None) => {
+                return Err(Error::InvalidProcessorOutput(
+                    "version is not a number".to_string(),
+                ))
+            }
+            None => {
+                return Err(Error::InvalidProcessorOutput(
+                    "missing version field".to_string(),
+                ))
+            }
+        }
+
+        let output = serde_json::from_value::<Self>(value)?;
+        output.validate()?;
+
+        Ok(output)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.title.trim().is_empty() {
+            return Err(Error::InvalidProcessorOutput("title is empty".to_string()));
+        }
+
+        if let Some(id) = &self.id {
+            if id.is_empty() || id.starts_with('/') || id.split('/').any(|part| part == "..") {
+                return Err(Error::InvalidProcessorOutput(format!(
+                    "id {:?} is not a relative path",
+                    id
+                )));
+            }
+        }
+
+        for (index, spec) in self.specs.iter().enumerate() {
+            if spec.id.is_empty() {
+                return Err(Error::InvalidProcessorOutput(format!(
+                    "spec #{} has an empty id",
+                    index
+                )));
+            }
+
+            if self.specs[..index].iter().any(|other| other.id == spec.id) {
+                return Err(Error::InvalidProcessorOutput(format!(
+                    "spec {} is declared more than once",
+                    spec.id
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Run an external processor on `rel_path` and parse its output. Server
+/// processors are asked through the worker of the current thread, falling
+/// back to a one-off run if the processor does not support server mode.
+pub fn process(
+    processor: &ProcessorConfig,
+    site: &SiteMetadata,
+    rel_path: &Path,
+) -> Result<ProcessorOutput, Error> {
+    if processor.server {
+        if let Some(output) = worker::process(processor, site, rel_path)? {
+            return Ok(output);
+        }
+    }
+
+    let stdout = run(processor, site, rel_path)?;
+
+    ProcessorOutput::parse(&stdout)
+}
+
+fn command_line(command: &Command) -> String {
+    let mut parts = vec![command.get_program().to_string_lossy().into_owned()];
+    parts.extend(
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned()),
+    );
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(value: Value) -> ProcessorConfig {
+        serde_json::from_value(value).expect("processor config is valid; qed")
+    }
+
+    #[test]
+    fn validate_any_extension_with_command() {
+        let processor = config(serde_json::json!({ "command": "eighty-rst" }));
+
+        assert_eq!(processor.typ, None);
+        assert!(processor.validate("rst").is_ok());
+        assert!(default_processors()
+            .iter()
+            .all(|(extension, processor)| processor.validate(extension).is_ok()));
+    }
+
+    #[test]
+    fn validate_builtin_only_for_own_extension() {
+        let markdown = config(serde_json::json!({ "type": "markdown" }));
+
+        assert!(markdown.validate("md").is_ok());
+        assert!(matches!(
+            markdown.validate("rst"),
+            Err(Error::ProcessorWithoutCommand(extension)) if extension == "rst"
+        ));
+        assert!(markdown.validate("org").is_err());
+        assert!(config(serde_json::json!({})).validate("md").is_err());
+    }
+
+    fn output(fields: Value) -> Result<ProcessorOutput, Error> {
+        let mut value = serde_json::json!({
+            "version": OUTPUT_VERSION,
+            "title": "Intro",
+            "content": "<p>Hello</p>",
+        });
+        for (key, field) in fields.as_object().expect("fields are an object; qed") {
+            value[key] = field.clone();
+        }
+
+        ProcessorOutput::from_value(value)
+    }
+
+    fn invalid_reason(result: Result<ProcessorOutput, Error>) -> String {
+        match result {
+            Err(Error::InvalidProcessorOutput(reason)) => reason,
+            other => panic!("expected invalid processor output, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_value_v1() {
+        let output = output(serde_json::json!({
+            "id": "guide/intro",
+            "dependencies": ["_inc/header.adoc"],
+            "specs": [{
+                "id": "1",
+                "description": "First",
+                "discuss": "",
+                "sourcePath": "guide.adoc",
+                "anchor": "first",
+            }],
+            "metadata": { "noindex": true },
+        }))
+        .expect("output is valid; qed");
+
+        assert_eq!(output.version, 1);
+        assert_eq!(output.id.as_deref(), Some("guide/intro"));
+        assert_eq!(output.description, "");
+        assert_eq!(output.dependencies, vec![PathBuf::from("_inc/header.adoc")]);
+        assert_eq!(output.specs[0].source_path, "guide.adoc");
+        assert_eq!(output.metadata["noindex"], Value::Bool(true));
+    }
+
+    #[test]
+    fn from_value_checks_version() {
+        assert_eq!(
+            invalid_reason(output(serde_json::json!({ "version": 2 }))),
+            "unsupported version 2, expected 1"
+        );
+        assert_eq!(
+            invalid_reason(output(serde_json::json!({ "version": "1" }))),
+            "version is not a number"
+        );
+        assert_eq!(
+            invalid_reason(ProcessorOutput::from_value(serde_json::json!({
+                "title": "Intro",
+                "content": "",
+            }))),
+            "missing version field"
+        );
+    }
+
+    #[test]
+    fn from_value_rejects_unknown_fields() {
+        assert!(matches!(
+            output(serde_json::json!({ "subtitle": "Unknown" })),
+            Err(Error::Json(_))
+        ));
+        assert!(matches!(
+            output(serde_json::json!({
+                "specs": [{
+                    "id": "1",
+                    "description": "",
+                    "discuss": "",
+                    "sourcePath": "",
+                    "anchor": "",
+                    "url": "",
+                }],
+            })),
+            Err(Error::Json(_))
+        ));
+    }
+
+    #[test]
+    fn validate_fields() {
+        assert_eq!(
+            invalid_reason(output(serde_json::json!({ "title": " " }))),
+            "title is empty"
+        );
+        assert_eq!(
+            invalid_reason(output(serde_json::json!({ "id": "../outside" }))),
+            "id \"../outside\" is not a relative path"
+        );
+
+        let spec = serde_json::json!({
+            "id": "1",
+            "description": "",
+            "discuss": "",
+            "sourcePath": "",
+            "anchor": "",
+        });
+        assert_eq!(
+            invalid_reason(output(serde_json::json!({ "specs": [spec, spec] }))),
+            "spec 1 is declared more than once"
+        );
+    }
+}
@@ -0,0 +1,345 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! Long-running processors, one per processor and rendering thread, handling
+//! one document per line over standard input and output. The protocol is
+//! described in the "Processor servers" section of the README.
+
+use super::{command_line, print_warnings, ProcessorConfig, ProcessorOutput, OUTPUT_VERSION};
+use crate::{site::SiteMetadata, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    cell::RefCell,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// How long a processor started with `--server` has to announce itself
+/// before it is run per document instead.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long a processor has to answer a request before it is stopped and
+/// the document is run on its own instead.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long to wait for the last lines of standard error of a stopped
+/// processor.
+const STDERR_TIMEOUT: Duration = Duration::from_millis(100);
+
+thread_local! {
+    static WORKERS: RefCell<Vec<Worker>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkerRequest<'a> {
+    root: &'a Path,
+    path: &'a Path,
+}
+
+/// First line printed by a processor in server mode.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct WorkerHandshake {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct WorkerResponse {
+    output: Option<Value>,
+    error: Option<String>,
+    #[serde(default)]
+    warnings: Vec<String>,
+}
+
+/// Reply of a worker to one request.
+enum WorkerReply {
+    Response(WorkerResponse),
+    /// The worker exited or did not answer with a response.
+    Gone,
+    /// The worker did not answer within `REQUEST_TIMEOUT`.
+    TimedOut,
+}
+
+struct Worker {
+    processor: ProcessorConfig,
+    command_line: String,
+    state: WorkerState,
+}
+
+enum WorkerState {
+    Stopped,
+    Running {
+        child: Child,
+        stdin: ChildStdin,
+        lines: Receiver<String>,
+        stderr: Receiver<String>,
+        handled: usize,
+    },
+    Unsupported,
+}
+
+impl Worker {
+    fn new(processor: &ProcessorConfig) -> Self {
+        let mut command = Command::new(processor.command());
+        command.args(&processor.args).arg("--server");
+
+        Self {
+            processor: processor.clone(),
+            command_line: command_line(&command),
+            state: WorkerState::Stopped,
+        }
+    }
+
+    fn start(&mut self, site: &SiteMetadata) {
+        let child = Command::new(self.processor.command())
+            .args(&self.processor.args)
+            .arg("--server")
+            .envs(&self.processor.env)
+            .current_dir(&site.source_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+
+        let (mut child, stdin, stdout, stderr) = match child {
+            Ok(mut child) => match (child.stdin.take(), child.stdout.take(), child.stderr.take()) {
+                (Some(stdin), Some(stdout), Some(stderr)) => (child, stdin, stdout, stderr),
+                _ => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    self.state = WorkerState::Unsupported;
+                    return;
+                }
+            },
+            Err(_) => {
+                self.state = WorkerState::Unsupported;
+                return;
+            }
+        };
+
+        // Lines are read on their own thread, so that waiting for the
+        // handshake can time out on processors that ignore `--server`.
+        let lines = read_lines(stdout);
+        let stderr = read_lines(stderr);
+
+        let handshake = lines
+            .recv_timeout(HANDSHAKE_TIMEOUT)
+            .ok()
+            .and_then(|line| serde_json::from_str::<WorkerHandshake>(&line).ok());
+
+        self.state = match handshake {
+            Some(handshake) if handshake.version == OUTPUT_VERSION => WorkerState::Running {
+                child,
+                stdin,
+                lines,
+                stderr,
+                handled: 0,
+            },
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                WorkerState::Unsupported
+            }
+        };
+    }
+
+    /// Send one request, returning the reply and what the worker wrote to
+    /// standard error meanwhile. A worker that went away without answering
+    /// is either restarted on the next request or, if it never answered at
+    /// all, given up on.
+    fn request(&mut self, site: &SiteMetadata, rel_path: &Path) -> (WorkerReply, Vec<String>) {
+        if let WorkerState::Stopped = self.state {
+            self.start(site);
+        }
+
+        let (reply, handled) = match &mut self.state {
+            WorkerState::Running {
+                stdin,
+                lines,
+                stderr,
+                handled,
+                ..
+            } => match exchange(stdin, lines, &site.source_path, rel_path) {
+                WorkerReply::Response(response) => {
+                    *handled += 1;
+                    let stderr = stderr.try_iter().collect();
+                    return (WorkerReply::Response(response), stderr);
+                }
+                reply => (reply, *handled),
+            },
+            WorkerState::Stopped | WorkerState::Unsupported => {
+                return (WorkerReply::Gone, Vec::new())
+            }
+        };
+
+        let stderr = self.stop();
+        if handled == 0 {
+            if let WorkerReply::Gone = reply {
+                self.state = WorkerState::Unsupported;
+            }
+        }
+
+        (reply, stderr)
+    }
+
+    /// Stop the worker, returning the rest of its standard error.
+    fn stop(&mut self) -> Vec<String> {
+        match std::mem::replace(&mut self.state, WorkerState::Stopped) {
+            WorkerState::Running {
+                mut child, stderr, ..
+            } => {
+                let _ = child.kill();
+                let _ = child.wait();
+
+                let mut lines = Vec::new();
+                while let Ok(line) = stderr.recv_timeout(STDERR_TIMEOUT) {
+                    lines.push(line);
+                }
+                lines
+            }
+            state => {
+                self.state = state;
+                Vec::new()
+            }
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Read lines of `reader` on their own thread.
+fn read_lines(reader: impl Read + Send + 'static) -> Receiver<String> {
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    lines
+}
+
+fn exchange(
+    stdin: &mut ChildStdin,
+    lines: &Receiver<String>,
+    root: &Path,
+    rel_path: &Path,
+) -> WorkerReply {
+    let request = serde_json::to_vec(&WorkerRequest {
+        root,
+        path: rel_path,
+    });
+    let sent = request.ok().and_then(|mut request| {
+        request.push(b'\n');
+        stdin.write_all(&request).ok()?;
+        stdin.flush().ok()
+    });
+    if sent.is_none() {
+        return WorkerReply::Gone;
+    }
+
+    match lines.recv_timeout(REQUEST_TIMEOUT) {
+        Ok(line) => match serde_json::from_str(&line) {
+            Ok(response) => WorkerReply::Response(response),
+            Err(_) => WorkerReply::Gone,
+        },
+        Err(RecvTimeoutError::Timeout) => WorkerReply::TimedOut,
+        Err(RecvTimeoutError::Disconnected) => WorkerReply::Gone,
+    }
+}
+
+/// Process a document with the worker of the current thread. Returns `None`
+/// if the document should be processed with a one-off run instead.
+pub fn process(
+    processor: &ProcessorConfig,
+    site: &SiteMetadata,
+    rel_path: &Path,
+) -> Result<Option<ProcessorOutput>, Error> {
+    WORKERS.with(|workers| {
+        let mut workers = workers.borrow_mut();
+        let index = match workers.iter().position(|w| &w.processor == processor) {
+            Some(index) => index,
+            None => {
+                workers.push(Worker::new(processor));
+                workers.len() - 1
+            }
+        };
+        let worker = &mut workers[index];
+        if let WorkerState::Unsupported = worker.state {
+            return Ok(None);
+        }
+
+        let (reply, stderr) = worker.request(site, rel_path);
+        let response = match reply {
+            WorkerReply::Response(response) => response,
+            WorkerReply::Gone => {
+                if let WorkerState::Unsupported = worker.state {
+                    println!(
+                        "[{}] warning: `{}` does not support server mode, running it per document",
+                        site.name, worker.command_line
+                    );
+                }
+                print_warnings(site, rel_path, stderr.iter().map(|line| line.as_str()));
+                return Ok(None);
+            }
+            WorkerReply::TimedOut => {
+                println!(
+                    "[{}] warning: `{}` did not answer within {} seconds, running it for {} alone",
+                    site.name,
+                    worker.command_line,
+                    REQUEST_TIMEOUT.as_secs(),
+                    rel_path.display()
+                );
+                print_warnings(site, rel_path, stderr.iter().map(|line| line.as_str()));
+                workers.remove(index);
+                return Ok(None);
+            }
+        };
+
+        print_warnings(site, rel_path, response.warnings.iter().map(|w| w.as_str()));
+
+        match (response.output, response.error) {
+            (_, Some(message)) => Err(Error::ProcessorFailed {
+                command: worker.command_line.clone(),
+                message,
+                stderr: stderr.join("\n"),
+            }),
+            (Some(output), None) => {
+                print_warnings(site, rel_path, stderr.iter().map(|line| line.as_str()));
+                Ok(Some(ProcessorOutput::from_value(output)?))
+            }
+            (None, None) => Err(Error::ProcessorFailed {
+                command: worker.command_line.clone(),
+                message: "response has neither output nor error".to_string(),
+                stderr: stderr.join("\n"),
+            }),
+        }
+    })
+}
@@ -16,9 +16,10 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::Error;
+use crate::{processor::ProcessorConfig, Error, ErrorContext};
 use serde::{de::Deserializer, Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     fmt,
     fs::File,
     io::BufReader,
@@ -47,6 +48,14 @@ pub struct SiteConfig {
     pub sitemap: SiteConfigSitemap,
     #[serde(default)]
     pub links: Vec<SiteConfigLink>,
+    #[serde(default)]
+    pub robots: SiteConfigRobots,
+    #[serde(default)]
+    pub feeds: SiteConfigFeeds,
+    #[serde(default)]
+    pub posts: SiteConfigPosts,
+    #[serde(default)]
+    pub processors: HashMap<String, ProcessorConfig>,
 }
 
 fn deserialize_site_url<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -71,6 +80,63 @@ pub struct SiteConfigLink {
     pub url: String,
 }
 
+/// Rules for the generated `robots.txt`.
+#[derive(Eq, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteConfigRobots {
+    /// Paths crawlers are asked not to visit, as written in `robots.txt`.
+    #[serde(default)]
+    pub disallow: Vec<String>,
+}
+
+/// Feeds of posts, generated for sites with `_posts` folders. Atom feeds are
+/// always generated, RSS and JSON Feed on request.
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteConfigFeeds {
+    /// Number of most recent posts in each feed.
+    #[serde(default = "default_feed_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub rss: bool,
+    #[serde(default)]
+    pub json: bool,
+}
+
+fn default_feed_limit() -> usize {
+    20
+}
+
+impl Default for SiteConfigFeeds {
+    fn default() -> Self {
+        Self {
+            limit: default_feed_limit(),
+            rss: false,
+            json: false,
+        }
+    }
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteConfigPosts {
+    /// Number of posts on each page of a posts listing.
+    #[serde(default = "default_posts_page_size")]
+    pub page_size: usize,
+}
+
+fn default_posts_page_size() -> usize {
+    10
+}
+
+impl Default for SiteConfigPosts {
+    fn default() -> Self {
+        Self {
+            page_size: default_posts_page_size(),
+        }
+    }
+}
+
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SiteConfigSitemap {
@@ -78,6 +144,19 @@ pub struct SiteConfigSitemap {
     pub depth: Option<usize>,
     #[serde(default)]
     pub extra_links: Vec<SiteConfigSitemapExtraLink>,
+    #[serde(default)]
+    pub navigation: SiteConfigNavigation,
+}
+
+/// Which documents the previous and next links of a document lead to.
+#[derive(Eq, Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SiteConfigNavigation {
+    /// Walk the whole sitemap depth-first, like the chapters of a book.
+    #[default]
+    Sitemap,
+    /// Only move between documents with the same parent.
+    Siblings,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -92,21 +171,35 @@ pub struct SiteMetadata {
     pub name: SiteName,
     pub source_path: PathBuf,
     pub config: SiteConfig,
+    pub processors: HashMap<String, ProcessorConfig>,
 }
 
 impl SiteMetadata {
-    pub fn new(name: SiteName, path: &Path) -> Result<SiteMetadata, Error> {
+    pub fn new(
+        name: SiteName,
+        path: &Path,
+        workspace_processors: &HashMap<String, ProcessorConfig>,
+    ) -> Result<SiteMetadata, Error> {
         if name.0 == "specs" {
-            return Err(Error::ReservedSiteName);
+            return Err(Error::ReservedSiteName(name));
         }
 
         let site_config_path = path.join("_site.json");
-        let site_config = serde_json::from_reader(BufReader::new(File::open(site_config_path)?))?;
+        let site_config_file = File::open(&site_config_path).path_context(&site_config_path)?;
+        let site_config: SiteConfig = serde_json::from_reader(BufReader::new(site_config_file))
+            .path_context(&site_config_path)?;
+
+        let mut processors = workspace_processors.clone();
+        processors.extend(site_config.processors.clone());
+        for (extension, processor) in &processors {
+            processor.validate(extension)?;
+        }
 
         let site = SiteMetadata {
             name: name.clone(),
             source_path: path.to_owned(),
             config: site_config,
+            processors,
         };
 
         Ok(site)
@@ -16,13 +16,15 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::document::DocumentName;
-use std::{fmt, ops::Deref};
+use crate::{document::DocumentName, site::SiteConfigNavigation};
+use std::{cmp::Ordering, fmt, ops::Deref};
 
 #[derive(Eq, Clone, PartialEq, Debug)]
 pub struct LocalSitemap {
     pub breadcrumb: Breadcrumb,
     pub children: Vec<BreadcrumbItem>,
+    pub previous: Option<BreadcrumbItem>,
+    pub next: Option<BreadcrumbItem>,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug)]
@@ -41,6 +43,37 @@ pub struct BreadcrumbItem {
     pub title: String,
     pub document_name: DocumentName,
     pub description: String,
+    pub order: Option<usize>,
+}
+
+impl BreadcrumbItem {
+    /// Order among siblings: documents with an explicit `order` first, then
+    /// by title and name, followed by posts, newest first.
+    pub fn cmp_siblings(&self, other: &Self) -> Ordering {
+        let post_date = |item: &Self| {
+            item.document_name
+                .post
+                .as_ref()
+                .map(|post| post.date.clone())
+        };
+
+        let by_order = match (self.order, other.order) {
+            (Some(order), Some(other_order)) => order.cmp(&other_order),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+
+        match (post_date(self), post_date(other)) {
+            (Some(date), Some(other_date)) => other_date.cmp(&date),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        }
+        .then(by_order)
+        .then_with(|| self.title.cmp(&other.title))
+        .then_with(|| self.document_name.cmp(&other.document_name))
+    }
 }
 
 #[derive(Eq, Clone, PartialEq, Debug)]
@@ -50,6 +83,14 @@ pub struct SitemapItem {
 }
 
 impl SitemapItem {
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| a.item.cmp_siblings(&b.item));
+
+        for child in &mut self.children {
+            child.sort();
+        }
+    }
+
     pub fn maybe_insert(&mut self, item: BreadcrumbItem) -> bool {
         if self.item.document_name.is_ancestor_of(&item.document_name) {
             let mut inserted = false;
@@ -97,11 +138,26 @@ impl Sitemap {
         }
     }
 
-    pub fn local(&self, document_name: &DocumentName) -> Option<LocalSitemap> {
+    /// Navigation of `document_name`. With `Sitemap` navigation, the
+    /// previous and next documents are its neighbours in `reading_order`, as
+    /// returned by `Sitemap::reading_order`.
+    pub fn local(
+        &self,
+        document_name: &DocumentName,
+        navigation: SiteConfigNavigation,
+        reading_order: &[&BreadcrumbItem],
+    ) -> Option<LocalSitemap> {
         if document_name.is_root() {
+            let (previous, next) = match navigation {
+                SiteConfigNavigation::Sitemap => neighbours(reading_order, document_name),
+                SiteConfigNavigation::Siblings => (None, None),
+            };
+
             return Some(LocalSitemap {
                 breadcrumb: Breadcrumb(Vec::new()),
                 children: self.0.iter().map(|item| item.item.clone()).collect(),
+                previous,
+                next,
             });
         }
 
@@ -110,12 +166,20 @@ impl Sitemap {
 
         loop {
             let target = current.iter().find(|item| {
-                item.item.document_name.is_ancestor_of(&document_name)
+                item.item.document_name.is_ancestor_of(document_name)
                     || item.item.document_name == *document_name
             });
 
             if let Some(target) = target {
                 if target.item.document_name == *document_name {
+                    let (previous, next) = match navigation {
+                        SiteConfigNavigation::Sitemap => neighbours(reading_order, document_name),
+                        SiteConfigNavigation::Siblings => neighbours(
+                            &current.iter().map(|item| &item.item).collect::<Vec<_>>(),
+                            document_name,
+                        ),
+                    };
+
                     return Some(LocalSitemap {
                         breadcrumb: Breadcrumb(breadcrumb),
                         children: target
@@ -123,6 +187,8 @@ impl Sitemap {
                             .iter()
                             .map(|item| item.item.clone())
                             .collect(),
+                        previous,
+                        next,
                     });
                 } else {
                     breadcrumb.push(target.item.clone());
@@ -134,10 +200,56 @@ impl Sitemap {
             }
         }
     }
+
+    /// All items in a depth-first walk, each followed by its children.
+    pub fn walk(&self) -> Vec<&BreadcrumbItem> {
+        fn walk_item<'a>(item: &'a SitemapItem, items: &mut Vec<&'a BreadcrumbItem>) {
+            items.push(&item.item);
+            for child in &item.children {
+                walk_item(child, items);
+            }
+        }
+
+        let mut items = Vec::new();
+        for item in &self.0 {
+            walk_item(item, &mut items);
+        }
+        items
+    }
+
+    /// Order in which documents are read like a book: the site root, which
+    /// the sitemap leaves out, followed by the walk of all other items.
+    pub fn reading_order<'a>(
+        &'a self,
+        root: Option<&'a BreadcrumbItem>,
+    ) -> Vec<&'a BreadcrumbItem> {
+        root.into_iter().chain(self.walk()).collect()
+    }
+}
+
+/// Items right before and after `document_name` in `items`.
+fn neighbours(
+    items: &[&BreadcrumbItem],
+    document_name: &DocumentName,
+) -> (Option<BreadcrumbItem>, Option<BreadcrumbItem>) {
+    match items
+        .iter()
+        .position(|item| item.document_name == *document_name)
+    {
+        Some(index) => (
+            index
+                .checked_sub(1)
+                .and_then(|index| items.get(index))
+                .map(|item| (*item).clone()),
+            items.get(index + 1).map(|item| (*item).clone()),
+        ),
+        None => (None, None),
+    }
 }
 
 impl From<Vec<BreadcrumbItem>> for Sitemap {
     fn from(mut name_titles: Vec<BreadcrumbItem>) -> Sitemap {
+        // Parents must be inserted before their children.
         name_titles.sort_by_key(|k| k.document_name.clone());
         let ordered_name_titles = name_titles;
 
@@ -148,6 +260,11 @@ impl From<Vec<BreadcrumbItem>> for Sitemap {
             }
         }
 
+        sitemap.0.sort_by(|a, b| a.item.cmp_siblings(&b.item));
+        for item in &mut sitemap.0 {
+            item.sort();
+        }
+
         sitemap
     }
 }
@@ -171,9 +288,9 @@ impl fmt::Display for Sitemap {
 }
 
 fn fmt_sitemap_item(f: &mut fmt::Formatter<'_>, item: &SitemapItem, prefix: &str) -> fmt::Result {
-    write!(
+    writeln!(
         f,
-        "{}- {}: {}\n",
+        "{}- {}: {}",
         prefix, item.item.document_name, item.item.title
     )?;
     for child in &item.children {
@@ -182,3 +299,147 @@ fn fmt_sitemap_item(f: &mut fmt::Formatter<'_>, item: &SitemapItem, prefix: &str
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::DocumentPostLabel;
+
+    fn item(label: &str, title: &str, order: Option<usize>) -> BreadcrumbItem {
+        BreadcrumbItem {
+            title: title.to_owned(),
+            document_name: DocumentName {
+                id: None,
+                labels: vec![label.to_owned()],
+                post: None,
+            },
+            description: String::new(),
+            order,
+        }
+    }
+
+    fn post(date: &str, label: &str) -> BreadcrumbItem {
+        BreadcrumbItem {
+            title: label.to_owned(),
+            document_name: DocumentName {
+                id: None,
+                labels: vec!["blog".to_owned()],
+                post: Some(DocumentPostLabel {
+                    date: date.to_owned(),
+                    label: label.to_owned(),
+                }),
+            },
+            description: String::new(),
+            order: None,
+        }
+    }
+
+    fn sorted(mut items: Vec<BreadcrumbItem>) -> Vec<String> {
+        items.sort_by(|a, b| a.cmp_siblings(b));
+        items.into_iter().map(|item| item.title).collect()
+    }
+
+    #[test]
+    fn cmp_siblings_explicit_order_first() {
+        assert_eq!(
+            sorted(vec![
+                item("a", "Alpha", None),
+                item("c", "Gamma", Some(2)),
+                item("b", "Beta", Some(1)),
+            ]),
+            vec!["Beta", "Gamma", "Alpha"]
+        );
+    }
+
+    #[test]
+    fn cmp_siblings_by_title_then_name() {
+        assert_eq!(
+            sorted(vec![
+                item("z", "Same", None),
+                item("b", "Beta", None),
+                item("a", "Same", None),
+            ]),
+            vec!["Beta", "Same", "Same"]
+        );
+
+        let mut items = [item("z", "Same", None), item("a", "Same", None)];
+        items.sort_by(|a, b| a.cmp_siblings(b));
+        assert_eq!(items[0].document_name.labels, vec!["a"]);
+    }
+
+    #[test]
+    fn cmp_siblings_posts_last_newest_first() {
+        assert_eq!(
+            sorted(vec![
+                post("2021-01-01", "old"),
+                item("a", "Alpha", None),
+                post("2022-05-01", "new"),
+                item("b", "Beta", Some(1)),
+            ]),
+            vec!["Beta", "Alpha", "new", "old"]
+        );
+    }
+
+    #[test]
+    fn neighbours_in_the_middle() {
+        let (a, b, c) = (
+            item("a", "A", None),
+            item("b", "B", None),
+            item("c", "C", None),
+        );
+        let items = vec![&a, &b, &c];
+
+        assert_eq!(
+            neighbours(&items, &b.document_name),
+            (Some(a.clone()), Some(c.clone()))
+        );
+    }
+
+    #[test]
+    fn neighbours_at_the_ends() {
+        let (a, b) = (item("a", "A", None), item("b", "B", None));
+        let items = vec![&a, &b];
+
+        assert_eq!(
+            neighbours(&items, &a.document_name),
+            (None, Some(b.clone()))
+        );
+        assert_eq!(
+            neighbours(&items, &b.document_name),
+            (Some(a.clone()), None)
+        );
+    }
+
+    #[test]
+    fn neighbours_of_missing_document() {
+        let (a, b) = (item("a", "A", None), item("b", "B", None));
+
+        assert_eq!(neighbours(&[&a], &b.document_name), (None, None));
+    }
+
+    #[test]
+    fn reading_order_starts_at_root() {
+        let root = BreadcrumbItem {
+            title: "Home".to_owned(),
+            document_name: DocumentName {
+                id: None,
+                labels: Vec::new(),
+                post: None,
+            },
+            description: String::new(),
+            order: None,
+        };
+        let sitemap = Sitemap::from(vec![
+            root.clone(),
+            item("b", "B", None),
+            item("a", "A", None),
+        ]);
+
+        let titles = sitemap
+            .reading_order(Some(&root))
+            .into_iter()
+            .map(|item| item.title.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Home", "A", "B"]);
+    }
+}
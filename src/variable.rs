@@ -29,7 +29,7 @@ pub struct Variable {
 pub fn search(content: &str) -> Result<Vec<Variable>, Error> {
     let mut matches = Vec::new();
 
-    let re = Regex::new(r"@@(.+)@@")?;
+    let re = Regex::new(r"@@(.+?)@@")?;
 
     for caps in re.captures_iter(content) {
         let full = caps
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::site::SiteName;
+use std::path::{Path, PathBuf};
+
+/// Paths that changed since the previous build, relative to the workspace
+/// root.
+#[derive(Eq, Clone, PartialEq, Debug, Default)]
+pub struct WorkspaceChanges {
+    paths: Vec<PathBuf>,
+}
+
+impl WorkspaceChanges {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self { paths }
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn workspace_config(&self) -> bool {
+        self.paths
+            .iter()
+            .any(|path| path == Path::new("_workspace.json"))
+    }
+
+    pub fn layouts(&self) -> bool {
+        self.paths
+            .iter()
+            .any(|path| path.starts_with("_assets/layouts"))
+    }
+
+    /// Whether any asset other than layouts changed.
+    pub fn static_assets(&self) -> bool {
+        self.paths
+            .iter()
+            .any(|path| path.starts_with("_assets") && !path.starts_with("_assets/layouts"))
+    }
+
+    /// Whether all changes are to stylesheets, which browsers can reload
+    /// without reloading the page.
+    pub fn stylesheets_only(&self) -> bool {
+        !self.paths.is_empty()
+            && self
+                .paths
+                .iter()
+                .all(|path| path.starts_with("_assets/css"))
+    }
+
+    /// Changed paths within a site, relative to the site folder. The site
+    /// folder itself shows up as an empty path.
+    pub fn site(&self, site_name: &SiteName) -> Vec<PathBuf> {
+        self.paths
+            .iter()
+            .filter_map(|path| path.strip_prefix(&site_name.0).ok())
+            .map(|path| path.to_owned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(paths: &[&str]) -> WorkspaceChanges {
+        WorkspaceChanges::new(paths.iter().map(PathBuf::from).collect())
+    }
+
+    #[test]
+    fn site_paths_relative_to_site() {
+        let changes = changes(&[
+            "docs",
+            "docs/guide/intro.md",
+            "blog/index.md",
+            "docsite/a.md",
+        ]);
+
+        assert_eq!(
+            changes.site(&SiteName("docs".to_owned())),
+            vec![PathBuf::new(), PathBuf::from("guide/intro.md")]
+        );
+        assert!(changes.site(&SiteName("other".to_owned())).is_empty());
+    }
+
+    #[test]
+    fn asset_changes() {
+        let layouts = changes(&["_assets/layouts/document/main.hbs"]);
+        assert!(layouts.layouts() && !layouts.static_assets());
+
+        let stylesheets = changes(&["_assets/css/main.css"]);
+        assert!(stylesheets.static_assets() && stylesheets.stylesheets_only());
+
+        let mixed = changes(&["_assets/css/main.css", "docs/index.md"]);
+        assert!(!mixed.stylesheets_only() && !mixed.workspace_config());
+        assert!(changes(&["_workspace.json"]).workspace_config());
+    }
+}
@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{processor::ProcessorConfig, Error, ErrorContext};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+/// Optional workspace-wide configuration, read from `_workspace.json` in the
+/// root folder.
+#[derive(Eq, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceConfig {
+    #[serde(default)]
+    pub processors: HashMap<String, ProcessorConfig>,
+    /// Where the `specs` site is published, ending with `/`. Needed for its
+    /// `sitemap.xml`, which takes absolute urls.
+    #[serde(default)]
+    pub specs_url: Option<String>,
+}
+
+impl WorkspaceConfig {
+    pub fn new(root_path: &Path) -> Result<WorkspaceConfig, Error> {
+        let config_path = root_path.join("_workspace.json");
+
+        if !config_path.exists() {
+            return Ok(WorkspaceConfig::default());
+        }
+
+        let config_file = File::open(&config_path).path_context(&config_path)?;
+        let config =
+            serde_json::from_reader(BufReader::new(config_file)).path_context(&config_path)?;
+
+        Ok(config)
+    }
+}
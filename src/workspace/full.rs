@@ -18,16 +18,18 @@
 
 use crate::{
     asset::AssetStore,
-    document::{DocumentMetadata, DocumentName, RenderedData, Spec},
+    document::{DocumentMetadata, DocumentName, RenderedData, RenderedDocument, Spec},
     file::FileMetadata,
-    layout,
-    site::{SiteMetadata, SiteName},
+    layout, link,
+    site::{SiteConfig, SiteConfigNavigation, SiteMetadata, SiteName},
     sitemap::{BreadcrumbItem, LocalSitemap, Sitemap},
     variable,
-    workspace::{RenderedSite, RenderedWorkspace},
-    Error,
+    workspace::{listing, RenderedSite, RenderedWorkspace, WorkspaceChanges},
+    xref::{self, SiteXrefs, UnresolvedXref, XrefKind, XrefTarget},
+    Error, ErrorContext,
 };
 use handlebars::Handlebars;
+use serde_json::Map;
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
@@ -37,22 +39,84 @@ use std::{
 pub struct FullWorkspace {
     pub root_path: PathBuf,
     pub assets: AssetStore,
+    pub xrefs: HashMap<SiteName, SiteXrefs>,
     pub sites: HashMap<SiteName, FullSite>,
     pub spec_site: FullSpecSite,
 }
 
 impl FullWorkspace {
-    pub fn new(rendered: &RenderedWorkspace) -> Result<Self, Error> {
+    pub fn new(rendered: &RenderedWorkspace, allow_unresolved_xrefs: bool) -> Result<Self, Error> {
         let assets = AssetStore::new(&rendered.root_path)?;
 
+        Self::new_with_assets(rendered, assets, None, allow_unresolved_xrefs)
+    }
+
+    /// Reuse assets and laid out documents of `old` where nothing they depend
+    /// on changed.
+    pub fn new_with_old(
+        rendered: &RenderedWorkspace,
+        old: &FullWorkspace,
+        changes: &WorkspaceChanges,
+        allow_unresolved_xrefs: bool,
+    ) -> Result<Self, Error> {
+        let assets = AssetStore::new_with_old(&rendered.root_path, &old.assets, changes)?;
+        let old = if changes.layouts() { None } else { Some(old) };
+
+        Self::new_with_assets(rendered, assets, old, allow_unresolved_xrefs)
+    }
+
+    fn new_with_assets(
+        rendered: &RenderedWorkspace,
+        assets: AssetStore,
+        old: Option<&FullWorkspace>,
+        allow_unresolved_xrefs: bool,
+    ) -> Result<Self, Error> {
+        let xrefs = rendered
+            .sites
+            .iter()
+            .map(|(name, site)| Ok((name.clone(), SiteXrefs::new(site).site_context(name)?)))
+            .collect::<Result<HashMap<SiteName, SiteXrefs>, Error>>()?;
+
+        // Documents may link to any site, so their layout can only be reused
+        // if no cross-reference target changed anywhere.
+        let old = old.filter(|old| old.xrefs == xrefs);
+
         let sites = rendered
             .sites
             .iter()
-            .map(|(name, site)| Ok((name.clone(), FullSite::new(&site, &assets.handlebars)?)))
+            .map(|(name, site)| {
+                let old_site = old.and_then(|old| old.sites.get(name));
+
+                Ok((
+                    name.clone(),
+                    FullSite::new_with_old(site, &xrefs, &assets.handlebars, old_site)
+                        .site_context(name)?,
+                ))
+            })
             .collect::<Result<HashMap<SiteName, FullSite>, Error>>()?;
 
+        let mut unresolved_xrefs = sites
+            .values()
+            .flat_map(|site| site.unresolved_xrefs.iter().cloned())
+            .collect::<Vec<_>>();
+        unresolved_xrefs.sort();
+
+        if !unresolved_xrefs.is_empty() {
+            if !allow_unresolved_xrefs {
+                return Err(Error::UnresolvedXrefs(unresolved_xrefs));
+            }
+
+            for unresolved_xref in &unresolved_xrefs {
+                println!("warning: {}", unresolved_xref);
+            }
+            println!(
+                "[workspace] {} unresolved cross-reference(s)",
+                unresolved_xrefs.len()
+            );
+        }
+
         let mut specs = HashMap::new();
-        for (_, site) in &sites {
+        for (site_name, site) in &sites {
             for document in &site.documents {
                 for spec in &document.rendered.specs {
                     let site_url = site.site.config.url.clone();
@@ -62,7 +126,9 @@ impl FullWorkspace {
                         document.rendered.name.folder_path().display()
                     );
                     let redirect_content =
-                        layout::spec_redirect(&spec, &redirect_url, &assets.handlebars)?;
+                        layout::spec_redirect(spec, &redirect_url, &assets.handlebars)
+                            .path_context(&document.metadata.rel_source_path)
+                            .site_context(site_name)?;
 
                     specs.insert(
                         spec.id.clone(),
@@ -76,11 +142,13 @@ impl FullWorkspace {
             }
         }
 
-        let spec_site = FullSpecSite::new(specs, &assets.handlebars)?;
+        let spec_site =
+            FullSpecSite::new(specs, rendered.config.specs_url.clone(), &assets.handlebars)?;
 
         Ok(Self {
             root_path: rendered.root_path.clone(),
             assets,
+            xrefs,
             sites,
             spec_site,
         })
@@ -91,101 +159,426 @@ pub struct FullSite {
     pub site: Arc<SiteMetadata>,
     pub documents: Vec<FullDocument>,
     pub files: Arc<HashMap<PathBuf, FileMetadata>>,
-    pub xrefs: HashMap<PathBuf, DocumentName>,
+    pub xrefs: HashMap<PathBuf, XrefTarget>,
+    pub unresolved_xrefs: Vec<UnresolvedXref>,
     pub sitemap: Sitemap,
+    /// Page served for missing paths.
+    pub not_found: Option<NotFoundPage>,
+    /// Generated post listing and archive pages, by path relative to the
+    /// site.
+    pub listings: HashMap<PathBuf, String>,
 }
 
 impl FullSite {
-    pub fn new(rendered: &RenderedSite, handlebars: &Handlebars) -> Result<Self, Error> {
+    pub fn new(
+        rendered: &RenderedSite,
+        workspace_xrefs: &HashMap<SiteName, SiteXrefs>,
+        handlebars: &Handlebars,
+    ) -> Result<Self, Error> {
+        Self::new_with_old(rendered, workspace_xrefs, handlebars, None)
+    }
+
+    /// Lay out a site, reusing documents of `old` that did not change. The
+    /// caller must only pass `old` if it was laid out with the same templates
+    /// and cross-reference targets.
+    pub fn new_with_old(
+        rendered: &RenderedSite,
+        workspace_xrefs: &HashMap<SiteName, SiteXrefs>,
+        handlebars: &Handlebars,
+        old: Option<&FullSite>,
+    ) -> Result<Self, Error> {
         let name_titles = rendered
             .documents
-            .iter()
-            .map(|(_, v)| BreadcrumbItem {
-                document_name: v.data.name.clone(),
-                title: v.data.sitemap_title.clone().unwrap_or(v.data.title.clone()),
-                description: v.data.description.clone(),
-            })
+            .values()
+            .filter(|v| !v.data.name.is_not_found())
+            .map(|v| breadcrumb_item(&v.data))
             .collect::<Vec<_>>();
 
         let sitemap = Sitemap::from(name_titles.clone());
+        let root = name_titles.iter().find(|item| item.document_name.is_root());
+        let reading_order = sitemap.reading_order(root);
 
-        let mut xrefs = HashMap::new();
-        for (_, document) in &rendered.documents {
-            let rel_path = document
-                .metadata
-                .source_path
-                .strip_prefix(&rendered.site.source_path)?;
-            xrefs.insert(rel_path.to_owned(), document.data.name.clone());
-        }
+        let xrefs = workspace_xrefs
+            .get(&rendered.site.name)
+            .ok_or_else(|| Error::SiteNotExist(rendered.site.name.clone()))?
+            .targets
+            .clone();
+
+        // The root is not part of the sitemap, but is in the reading order.
+        let old_documents = old
+            .filter(|old| {
+                let old_root = old
+                    .documents
+                    .iter()
+                    .find(|document| document.rendered.name.is_root())
+                    .map(|document| breadcrumb_item(&document.rendered));
+
+                old.site == rendered.site && old.sitemap == sitemap && old_root.as_ref() == root
+            })
+            .map(|old| {
+                old.documents
+                    .iter()
+                    .map(|document| (&document.metadata.rel_source_path, document))
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
 
         let full_documents = rendered
             .documents
-            .iter()
-            .map(|(_, v)| {
-                let local_sitemap = sitemap.local(&v.data.name).ok_or(Error::DocumentNotFound)?;
-
-                let mut content = layout::document(&v, &sitemap, &local_sitemap, handlebars)?;
-                let variables = variable::search(&content)?;
-
-                for variable in variables {
-                    if &variable.name == "XREFLINK" {
-                        if let Some(xreflink) = variable.arguments {
-                            let resolved = format!(
-                                "{}{}/",
-                                rendered.site.config.base_url,
-                                xrefs
-                                    .get(Path::new(&xreflink))
-                                    .ok_or(Error::UnresolvedXreflink)?
-                                    .folder_path()
-                                    .display()
-                            );
-
-                            content = content.replace(&variable.full, &resolved);
-                        } else {
-                            return Err(Error::UnsupportedVariable);
-                        }
-                    } else {
-                        return Err(Error::UnsupportedVariable);
+            .values()
+            .filter(|v| !v.data.name.is_not_found())
+            .map(|v| {
+                let old_document = old_documents.get(&v.metadata.rel_source_path);
+                if let Some(old_document) = old_document {
+                    if old_document.metadata == v.metadata && old_document.rendered == v.data {
+                        return Ok((*old_document).clone());
                     }
                 }
 
-                Ok(FullDocument {
-                    site_metadata: v.site_metadata.clone(),
-                    metadata: v.metadata.clone(),
-                    rendered: v.data.clone(),
-                    content,
-                    local_sitemap,
-                })
+                FullDocument::new(
+                    v,
+                    rendered,
+                    &sitemap,
+                    &reading_order,
+                    &xrefs,
+                    workspace_xrefs,
+                    handlebars,
+                )
             })
             .collect::<Result<Vec<FullDocument>, Error>>()?;
 
+        let not_found_document = rendered
+            .documents
+            .values()
+            .find(|v| v.data.name.is_not_found())
+            .map(|v| {
+                FullDocument::new(
+                    v,
+                    rendered,
+                    &sitemap,
+                    &reading_order,
+                    &xrefs,
+                    workspace_xrefs,
+                    handlebars,
+                )
+            })
+            .transpose()?;
+        let not_found = match &not_found_document {
+            Some(document) => Some(NotFoundPage::Document(document.content.clone())),
+            None if handlebars.has_template("document/404") => Some(NotFoundPage::Layout(
+                not_found_layout(rendered, &sitemap, handlebars)?,
+            )),
+            None => None,
+        };
+
+        let listings = listing::listings(&rendered.site, &sitemap, &full_documents, handlebars)?;
+
+        let unresolved_xrefs = full_documents
+            .iter()
+            .chain(not_found_document.iter())
+            .flat_map(|document| document.unresolved_xrefs.iter().cloned())
+            .collect();
+
         Ok(Self {
             site: rendered.site.clone(),
             documents: full_documents,
             files: rendered.files.clone(),
             xrefs,
+            unresolved_xrefs,
             sitemap,
+            not_found,
+            listings,
         })
     }
 }
 
+/// Content of the page served for missing paths, by where it comes from.
+#[derive(Eq, Clone, PartialEq, Debug)]
+pub enum NotFoundPage {
+    /// The site's `404` document.
+    Document(String),
+    /// The `document/404` layout alone.
+    Layout(String),
+}
+
+fn breadcrumb_item(data: &RenderedData) -> BreadcrumbItem {
+    BreadcrumbItem {
+        document_name: data.name.clone(),
+        title: data.sitemap_title.clone().unwrap_or(data.title.clone()),
+        description: data.description.clone(),
+        order: data.order,
+    }
+}
+
+/// Lay out a 404 page from the `document/404` layout alone, for sites
+/// without a `404` document.
+fn not_found_layout(
+    rendered: &RenderedSite,
+    sitemap: &Sitemap,
+    handlebars: &Handlebars,
+) -> Result<String, Error> {
+    let data = RenderedData {
+        name: DocumentName {
+            id: None,
+            labels: vec!["404".to_string()],
+            post: None,
+        },
+        title: "Page not found".to_string(),
+        sitemap_title: None,
+        order: None,
+        content: String::new(),
+        toc: None,
+        description: String::new(),
+        description_content: None,
+        license: None,
+        license_code: None,
+        specs: Vec::new(),
+        dependencies: Vec::new(),
+        metadata: Map::new(),
+    };
+    let local_sitemap = root_local_sitemap(sitemap)?;
+
+    layout::not_found(&rendered.site, &data, sitemap, &local_sitemap, handlebars)
+}
+
+/// The 404 page is not part of the sitemap, so it is shown with the
+/// navigation of the site root, without previous and next documents.
+fn root_local_sitemap(sitemap: &Sitemap) -> Result<LocalSitemap, Error> {
+    let root = DocumentName {
+        id: None,
+        labels: Vec::new(),
+        post: None,
+    };
+
+    sitemap
+        .local(&root, SiteConfigNavigation::Siblings, &[])
+        .ok_or(Error::DocumentNotFound(root))
+}
+
 #[derive(Eq, Clone, PartialEq, Debug)]
 pub struct FullDocument {
     pub site_metadata: Arc<SiteMetadata>,
     pub metadata: Arc<DocumentMetadata>,
     pub rendered: Arc<RenderedData>,
     pub content: String,
+    /// Content of a post without the layout, with links resolved, for feeds.
+    pub post_content: Option<String>,
     pub local_sitemap: LocalSitemap,
+    pub unresolved_xrefs: Vec<UnresolvedXref>,
+}
+
+impl FullDocument {
+    pub fn new(
+        v: &RenderedDocument,
+        rendered: &RenderedSite,
+        sitemap: &Sitemap,
+        reading_order: &[&BreadcrumbItem],
+        xrefs: &HashMap<PathBuf, XrefTarget>,
+        workspace_xrefs: &HashMap<SiteName, SiteXrefs>,
+        handlebars: &Handlebars,
+    ) -> Result<Self, Error> {
+        let mut unresolved_xrefs = Vec::new();
+
+        let document_path = &v.metadata.rel_source_path;
+
+        // Links to source files are only rewritten in the document itself,
+        // as links in layouts are not relative to it.
+        let linked = RenderedDocument {
+            data: Arc::new(RenderedData {
+                content: resolve_links(v.data.content.clone(), v, rendered, xrefs, false)?,
+                ..(*v.data).clone()
+            }),
+            ..v.clone()
+        };
+
+        let (local_sitemap, content) = if v.data.name.is_not_found() {
+            let local_sitemap = root_local_sitemap(sitemap).path_context(document_path)?;
+            let content = layout::not_found(
+                &v.site_metadata,
+                &linked.data,
+                sitemap,
+                &local_sitemap,
+                handlebars,
+            )
+            .path_context(document_path)?;
+            (local_sitemap, content)
+        } else {
+            let local_sitemap = sitemap
+                .local(
+                    &v.data.name,
+                    v.site_metadata.config.sitemap.navigation,
+                    reading_order,
+                )
+                .ok_or_else(|| Error::DocumentNotFound(v.data.name.clone()))
+                .path_context(document_path)?;
+            let content = layout::document(&linked, sitemap, &local_sitemap, handlebars)
+                .path_context(document_path)?;
+            (local_sitemap, content)
+        };
+        let content = resolve(
+            content,
+            v,
+            rendered,
+            xrefs,
+            workspace_xrefs,
+            false,
+            &mut unresolved_xrefs,
+        )?;
+        // Feed readers have no base URL to resolve site-relative links with.
+        let post_content = match v.data.name.post {
+            Some(_) => Some(resolve(
+                resolve_links(v.data.content.clone(), v, rendered, xrefs, true)?,
+                v,
+                rendered,
+                xrefs,
+                workspace_xrefs,
+                true,
+                &mut unresolved_xrefs,
+            )?),
+            None => None,
+        };
+
+        Ok(FullDocument {
+            site_metadata: v.site_metadata.clone(),
+            metadata: v.metadata.clone(),
+            rendered: v.data.clone(),
+            content,
+            post_content,
+            local_sitemap,
+            unresolved_xrefs,
+        })
+    }
+}
+
+/// Replace cross-reference macros in `content` with URLs, recording the
+/// macros that could not be resolved, including unknown macro names and
+/// macros without a target. With `full_urls`, links within the
+/// site are made absolute too.
+fn resolve(
+    mut content: String,
+    v: &RenderedDocument,
+    rendered: &RenderedSite,
+    xrefs: &HashMap<PathBuf, XrefTarget>,
+    workspace_xrefs: &HashMap<SiteName, SiteXrefs>,
+    full_urls: bool,
+    unresolved_xrefs: &mut Vec<UnresolvedXref>,
+) -> Result<String, Error> {
+    let document_path = &v.metadata.rel_source_path;
+    let variables = variable::search(&content).path_context(document_path)?;
+
+    for variable in variables {
+        let kind = XrefKind::try_from(variable.name.as_str()).ok();
+        let arguments = variable.arguments.as_deref();
+        let resolved = match (kind, arguments) {
+            (Some(kind), Some(arguments)) => match xref::parse(arguments) {
+                (Some(site_name), path) => workspace_xrefs.get(&site_name).and_then(|site_xrefs| {
+                    site_xrefs
+                        .targets
+                        .get(path)
+                        .map(|target| target.full_url(kind, &site_xrefs.site.config))
+                }),
+                (None, path) => xrefs.get(path).map(|target| {
+                    if full_urls {
+                        target.full_url(kind, &rendered.site.config)
+                    } else {
+                        target.url(kind, &rendered.site.config)
+                    }
+                }),
+            },
+            _ => None,
+        };
+
+        if let Some(resolved) = resolved {
+            content = content.replace(&variable.full, &resolved);
+        } else {
+            let unresolved_xref = UnresolvedXref {
+                site: rendered.site.name.clone(),
+                document: document_path.clone(),
+                variable: variable.full.clone(),
+                suggestion: match (kind, arguments) {
+                    (Some(_), Some(arguments)) => {
+                        xref::suggest(workspace_xrefs, &rendered.site.name, arguments)
+                    }
+                    (Some(_), None) => None,
+                    (None, _) => xref::suggest_kind(&variable.name),
+                },
+            };
+
+            if !unresolved_xrefs.contains(&unresolved_xref) {
+                unresolved_xrefs.push(unresolved_xref);
+            }
+        }
+    }
+
+    Ok(content)
+}
+
+/// Replace links to source files in the rendered content of an HTML
+/// document with the URLs of their targets, absolute with `full_urls`.
+fn resolve_links(
+    content: String,
+    v: &RenderedDocument,
+    rendered: &RenderedSite,
+    xrefs: &HashMap<PathBuf, XrefTarget>,
+    full_urls: bool,
+) -> Result<String, Error> {
+    if !v.metadata.is_html() {
+        return Ok(content);
+    }
+
+    rewrite_links(
+        content,
+        &v.metadata.rel_source_path,
+        xrefs,
+        &rendered.site.config,
+        full_urls,
+    )
+}
+
+/// Rewrite links in `content` relative to `document_path` that lead to a
+/// known target. Other links are left as they are.
+fn rewrite_links(
+    mut content: String,
+    document_path: &Path,
+    xrefs: &HashMap<PathBuf, XrefTarget>,
+    config: &SiteConfig,
+    full_urls: bool,
+) -> Result<String, Error> {
+    for link in link::search(&content).path_context(document_path)? {
+        let resolved = link
+            .resolve(document_path)
+            .and_then(|(path, fragment)| xrefs.get(&path).map(|target| (target, fragment)));
+
+        if let Some((target, fragment)) = resolved {
+            let mut url = if full_urls {
+                target.full_url(XrefKind::Link, config)
+            } else {
+                target.url(XrefKind::Link, config)
+            };
+            if let Some(fragment) = fragment {
+                url.push_str(&format!("#{}", fragment));
+            }
+
+            content = content.replace(&link.full, &format!("href=\"{}\"", url));
+        }
+    }
+
+    Ok(content)
 }
 
 #[derive(Eq, Clone, PartialEq, Debug)]
 pub struct FullSpecSite {
     pub specs: HashMap<String, FullSpec>,
     pub index_content: String,
+    pub url: Option<String>,
 }
 
 impl FullSpecSite {
-    pub fn new(specs: HashMap<String, FullSpec>, handlebars: &Handlebars) -> Result<Self, Error> {
+    pub fn new(
+        specs: HashMap<String, FullSpec>,
+        url: Option<String>,
+        handlebars: &Handlebars,
+    ) -> Result<Self, Error> {
         let sorted_specs = {
             let mut specs = specs
                 .values()
@@ -200,6 +593,7 @@ impl FullSpecSite {
         Ok(FullSpecSite {
             specs,
             index_content,
+            url,
         })
     }
 }
@@ -225,3 +619,103 @@ impl FullSpec {
         path
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SiteConfig {
+        serde_json::from_value(serde_json::json!({
+            "title": "Example",
+            "url": "https://example.com/docs/",
+            "baseUrl": "/docs/",
+            "author": "Example",
+            "email": "",
+            "sitemap": { "enable": true, "depth": null },
+        }))
+        .expect("site config is valid; qed")
+    }
+
+    fn xrefs() -> HashMap<PathBuf, XrefTarget> {
+        let document = |labels: &[&str]| {
+            XrefTarget::Document(DocumentName {
+                id: None,
+                labels: labels.iter().map(|label| label.to_string()).collect(),
+                post: None,
+            })
+        };
+
+        HashMap::from([
+            (PathBuf::from("index.md"), document(&[])),
+            (PathBuf::from("other/page.md"), document(&["other", "page"])),
+            (
+                PathBuf::from("guide/setup.md"),
+                document(&["guide", "setup"]),
+            ),
+            (
+                PathBuf::from("guide/diagram.svg"),
+                XrefTarget::File(PathBuf::from("guide/diagram.svg")),
+            ),
+        ])
+    }
+
+    fn rewrite(content: &str, full_urls: bool) -> String {
+        rewrite_links(
+            content.to_owned(),
+            Path::new("guide/intro.md"),
+            &xrefs(),
+            &config(),
+            full_urls,
+        )
+        .expect("links are valid; qed")
+    }
+
+    #[test]
+    fn rewrite_relative_links() {
+        assert_eq!(
+            rewrite(r#"<a href="../other/page.md">"#, false),
+            r#"<a href="/docs/other/page/">"#
+        );
+        assert_eq!(
+            rewrite(r#"<a href="setup.md">"#, false),
+            r#"<a href="/docs/guide/setup/">"#
+        );
+        assert_eq!(
+            rewrite(r#"<a href="../index.md">"#, false),
+            r#"<a href="/docs/">"#
+        );
+        assert_eq!(
+            rewrite(r#"<img href="./diagram.svg">"#, false),
+            r#"<img href="/docs/guide/diagram.svg">"#
+        );
+    }
+
+    #[test]
+    fn rewrite_keeps_fragments() {
+        assert_eq!(
+            rewrite(r#"<a href="setup.md#install">"#, false),
+            r#"<a href="/docs/guide/setup/#install">"#
+        );
+    }
+
+    #[test]
+    fn rewrite_full_urls() {
+        assert_eq!(
+            rewrite(r#"<a href="../other/page.md">"#, true),
+            r#"<a href="https://example.com/docs/other/page/">"#
+        );
+    }
+
+    #[test]
+    fn rewrite_leaves_other_links() {
+        for content in [
+            r#"<a href="https://example.com/setup.md">"#,
+            r#"<a href="/guide/setup.md">"#,
+            r##"<a href="#install">"##,
+            r#"<a href="missing.md">"#,
+            r#"<a href="../../setup.md">"#,
+        ] {
+            assert_eq!(rewrite(content, false), content);
+        }
+    }
+}
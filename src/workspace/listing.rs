@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! Generated listing and date archive pages of `_posts` folders.
+
+use super::FullDocument;
+use crate::{
+    document::DocumentName,
+    layout::{self, ArchiveMonth, ArchiveYear, PostsPage, PostsPageItem, PostsPageKind},
+    site::{SiteConfigNavigation, SiteMetadata},
+    sitemap::{LocalSitemap, Sitemap},
+    Error,
+};
+use handlebars::Handlebars;
+use std::{collections::HashMap, path::PathBuf};
+
+/// Lay out the listing pages of every `_posts` folder of a site with the
+/// `posts/list` layout, and its year and month archives with
+/// `posts/archive`. Pages are only generated for layouts that exist.
+pub fn listings(
+    site: &SiteMetadata,
+    sitemap: &Sitemap,
+    documents: &[FullDocument],
+    handlebars: &Handlebars,
+) -> Result<HashMap<PathBuf, String>, Error> {
+    let mut pages = HashMap::new();
+
+    let has_list = handlebars.has_template("posts/list");
+    let has_archive = handlebars.has_template("posts/archive");
+    if !has_list && !has_archive {
+        return Ok(pages);
+    }
+
+    let mut folders = HashMap::<&Vec<String>, Vec<&FullDocument>>::new();
+    for document in documents {
+        if document.rendered.name.post.is_some() {
+            folders
+                .entry(&document.rendered.name.labels)
+                .or_default()
+                .push(document);
+        }
+    }
+
+    let items = sitemap.walk();
+    let page_size = site.config.posts.page_size.max(1);
+
+    for (folder, mut posts) in folders {
+        // Newest first.
+        posts.sort_by(|a, b| b.rendered.name.post.cmp(&a.rendered.name.post));
+
+        let folder_name = DocumentName {
+            id: None,
+            labels: folder.clone(),
+            post: None,
+        };
+        let folder_path = folder_name.folder_path();
+        let local_sitemap = folder_local_sitemap(sitemap, &folder_name);
+        let folder_title = items
+            .iter()
+            .find(|item| item.document_name.post.is_none() && item.document_name.labels == *folder)
+            .map(|item| item.title.clone())
+            .unwrap_or_else(|| {
+                if folder.is_empty() {
+                    site.config.title.clone()
+                } else {
+                    folder.join("/")
+                }
+            });
+
+        let dated = posts
+            .iter()
+            .map(|document| {
+                let date = document
+                    .rendered
+                    .name
+                    .post
+                    .as_ref()
+                    .map(|post| post.date.as_str())
+                    .unwrap_or_default();
+                let mut parts = date.split('-');
+                let year = parts.next().unwrap_or_default().to_owned();
+                let month = parts.next().unwrap_or_default().to_owned();
+                (year, month, *document)
+            })
+            .collect::<Vec<_>>();
+
+        let mut archives = Vec::<ArchiveYear>::new();
+        for (year, month, _) in &dated {
+            if archives.last().map(|last| &last.year) != Some(year) {
+                archives.push(ArchiveYear {
+                    year: year.clone(),
+                    count: 0,
+                    months: Vec::new(),
+                });
+            }
+            let archive_year = archives.last_mut().expect("pushed above; qed");
+            archive_year.count += 1;
+
+            if archive_year.months.last().map(|last| &last.month) != Some(month) {
+                archive_year.months.push(ArchiveMonth {
+                    month: month.clone(),
+                    count: 0,
+                });
+            }
+            archive_year
+                .months
+                .last_mut()
+                .expect("pushed above; qed")
+                .count += 1;
+        }
+
+        if has_list {
+            let chunks = posts.chunks(page_size).collect::<Vec<_>>();
+            let total = chunks.len();
+
+            for (index, chunk) in chunks.into_iter().enumerate() {
+                let path = layout::list_page_path(&folder_path, index + 1);
+                let content = layout::posts(
+                    site,
+                    sitemap,
+                    &local_sitemap,
+                    &PostsPage {
+                        folder_title: folder_title.clone(),
+                        folder_name: folder_name.clone(),
+                        path: path.clone(),
+                        posts: chunk.iter().map(|document| page_item(document)).collect(),
+                        kind: PostsPageKind::List {
+                            page: index + 1,
+                            pages: total,
+                        },
+                        archives: &archives,
+                    },
+                    handlebars,
+                )?;
+
+                // The first page doubles as the folder index, unless the
+                // folder has an index document of its own.
+                if index == 0 {
+                    pages.insert(folder_path.join("index.html"), content.clone());
+                }
+                pages.insert(path.join("index.html"), content);
+            }
+        }
+
+        if has_archive {
+            for archive_year in &archives {
+                let months = archive_year
+                    .months
+                    .iter()
+                    .map(|archive_month| Some(archive_month.month.clone()));
+
+                for month in std::iter::once(None).chain(months) {
+                    let mut path = folder_path.join(&archive_year.year);
+                    if let Some(month) = &month {
+                        path.push(month);
+                    }
+
+                    let content = layout::posts(
+                        site,
+                        sitemap,
+                        &local_sitemap,
+                        &PostsPage {
+                            folder_title: folder_title.clone(),
+                            folder_name: folder_name.clone(),
+                            path: path.clone(),
+                            posts: dated
+                                .iter()
+                                .filter(|(year, post_month, _)| {
+                                    *year == archive_year.year
+                                        && month
+                                            .as_ref()
+                                            .map(|month| month == post_month)
+                                            .unwrap_or(true)
+                                })
+                                .map(|(_, _, document)| page_item(document))
+                                .collect(),
+                            kind: PostsPageKind::Archive {
+                                year: archive_year.year.clone(),
+                                month,
+                            },
+                            archives: &archives,
+                        },
+                        handlebars,
+                    )?;
+
+                    pages.insert(path.join("index.html"), content);
+                }
+            }
+        }
+    }
+
+    Ok(pages)
+}
+
+/// Navigation of the index document of a posts folder, or of the site root if
+/// it has none. Listings are not in the reading order, so they have no
+/// previous and next documents.
+fn folder_local_sitemap(sitemap: &Sitemap, folder_name: &DocumentName) -> LocalSitemap {
+    let root = DocumentName {
+        id: None,
+        labels: Vec::new(),
+        post: None,
+    };
+    let local_sitemap = sitemap
+        .local(folder_name, SiteConfigNavigation::Siblings, &[])
+        .or_else(|| sitemap.local(&root, SiteConfigNavigation::Siblings, &[]))
+        .expect("the root is always in the sitemap; qed");
+
+    LocalSitemap {
+        previous: None,
+        next: None,
+        ..local_sitemap
+    }
+}
+
+fn page_item(document: &FullDocument) -> PostsPageItem<'_> {
+    PostsPageItem {
+        data: &document.rendered,
+        content: document.post_content.as_deref().unwrap_or_default(),
+    }
+}
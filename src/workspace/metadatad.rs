@@ -16,16 +16,18 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+use super::{WorkspaceChanges, WorkspaceConfig};
 use crate::{
-    document::{DocumentMetadata, DocumentType},
+    document::DocumentMetadata,
     file::FileMetadata,
+    processor::{self, ProcessorConfig},
     site::{SiteMetadata, SiteName},
-    Error,
+    Error, ErrorContext,
 };
 use std::{
     collections::HashMap,
     fs,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     sync::Arc,
 };
 use walkdir::WalkDir;
@@ -33,6 +35,7 @@ use walkdir::WalkDir;
 #[derive(Eq, Clone, PartialEq, Debug)]
 pub struct MetadatadWorkspace {
     pub root_path: PathBuf,
+    pub config: WorkspaceConfig,
     pub sites: HashMap<SiteName, MetadatadSite>,
 }
 
@@ -40,33 +43,115 @@ impl MetadatadWorkspace {
     pub fn new(root_path: &Path) -> Result<Self, Error> {
         let mut sites = HashMap::new();
 
-        let root_subfolders = fs::read_dir(root_path)?;
+        let config = WorkspaceConfig::new(root_path)?;
+        let processors = workspace_processors(&config);
 
-        for site_folder in root_subfolders {
-            let site_folder = site_folder?;
-            let site_name = site_folder
-                .file_name()
-                .into_string()
-                .map_err(|_| Error::PathContainNonUnicode)?;
+        for (site_name, site_path) in site_folders(root_path)? {
+            let site = Arc::new(
+                SiteMetadata::new(site_name.clone(), &site_path, &processors)
+                    .site_context(&site_name)?,
+            );
+            let item = MetadatadSite::new(site.clone()).site_context(&site_name)?;
 
-            if site_name.starts_with(".") || site_name.starts_with("_") || site_name.ends_with("@tmp") {
-                continue;
-            }
+            sites.insert(site_name, item);
+        }
 
-            let site = Arc::new(SiteMetadata::new(
-                SiteName(site_name.clone()),
-                &site_folder.path(),
-            )?);
-            let item = MetadatadSite::new(site.clone())?;
+        Ok(Self {
+            sites,
+            config,
+            root_path: root_path.to_owned(),
+        })
+    }
 
-            sites.insert(SiteName(site_name), item);
+    /// Rescan only the sites and paths in `changes`, reusing everything else
+    /// from `old`.
+    pub fn new_with_old(
+        root_path: &Path,
+        old: &MetadatadWorkspace,
+        changes: &WorkspaceChanges,
+    ) -> Result<Self, Error> {
+        if changes.workspace_config() {
+            return Self::new(root_path);
+        }
+
+        let mut sites = HashMap::new();
+
+        let processors = workspace_processors(&old.config);
+
+        for (site_name, site_path) in site_folders(root_path)? {
+            let site_changes = changes.site(&site_name);
+            let reload = site_changes
+                .iter()
+                .any(|path| path == Path::new("") || path == Path::new("_site.json"));
+
+            let item = match old.sites.get(&site_name) {
+                Some(old_site) if site_changes.is_empty() => old_site.clone(),
+                Some(old_site) if !reload => {
+                    MetadatadSite::new_with_old(old_site, &site_changes).site_context(&site_name)?
+                }
+                _ => {
+                    let site = Arc::new(
+                        SiteMetadata::new(site_name.clone(), &site_path, &processors)
+                            .site_context(&site_name)?,
+                    );
+                    MetadatadSite::new(site).site_context(&site_name)?
+                }
+            };
+
+            sites.insert(site_name, item);
         }
 
         Ok(Self {
             sites,
+            config: old.config.clone(),
             root_path: root_path.to_owned(),
         })
     }
+
+    /// Replace the url and base url of every site, for example to preview
+    /// sites from a local server. `urls` returns the url and base url for a
+    /// site.
+    pub fn override_urls<F: Fn(&SiteName) -> (String, String)>(&mut self, urls: F) {
+        for (site_name, item) in self.sites.iter_mut() {
+            let (url, base_url) = urls(site_name);
+
+            if item.site.config.url != url || item.site.config.base_url != base_url {
+                let mut site = item.site.as_ref().clone();
+                site.config.url = url;
+                site.config.base_url = base_url;
+
+                item.site = Arc::new(site);
+            }
+        }
+    }
+}
+
+fn workspace_processors(config: &WorkspaceConfig) -> HashMap<String, ProcessorConfig> {
+    let mut processors = processor::default_processors();
+    processors.extend(config.processors.clone());
+    processors
+}
+
+fn site_folders(root_path: &Path) -> Result<Vec<(SiteName, PathBuf)>, Error> {
+    let mut folders = Vec::new();
+
+    let root_subfolders = fs::read_dir(root_path).path_context(root_path)?;
+
+    for site_folder in root_subfolders {
+        let site_folder = site_folder.path_context(root_path)?;
+        let site_name = site_folder
+            .file_name()
+            .into_string()
+            .map_err(|_| Error::PathContainNonUnicode(site_folder.path()))?;
+
+        if site_name.starts_with(".") || site_name.starts_with("_") || site_name.ends_with("@tmp") {
+            continue;
+        }
+
+        folders.push((SiteName(site_name), site_folder.path()));
+    }
+
+    Ok(folders)
 }
 
 #[derive(Eq, Clone, PartialEq, Debug)]
@@ -81,54 +166,29 @@ impl MetadatadSite {
         let mut documents = Vec::new();
         let mut files = HashMap::new();
 
-        let walker = WalkDir::new(&site.source_path)
-            .into_iter()
-            .filter_entry(|entry| {
-                if let Some(file_name) = entry.file_name().to_str() {
-                    if file_name == "_posts" && entry.file_type().is_dir() {
-                        return true;
-                    }
-
-                    if file_name.starts_with(".") || file_name.starts_with("_") {
-                        return false;
-                    }
-
-                    return true;
-                }
-
-                return false;
-            });
+        scan(&site, &site.source_path, &mut documents, &mut files)?;
 
-        for entry in walker {
-            let entry = entry?;
+        Ok(Self {
+            site,
+            documents,
+            files: Arc::new(files),
+        })
+    }
 
-            if entry.file_type().is_file() {
-                let modified = fs::metadata(entry.path())?.modified()?;
+    /// Update `old` for the changed paths, relative to the site folder. A
+    /// changed folder is rescanned as a whole.
+    pub fn new_with_old(old: &MetadatadSite, changes: &[PathBuf]) -> Result<Self, Error> {
+        let site = old.site.clone();
+        let mut documents = old.documents.clone();
+        let mut files = old.files.as_ref().clone();
 
-                let typ = if let Some(extension) = entry.path().extension() {
-                    let extension = extension.to_str().ok_or(Error::PathContainNonUnicode)?;
-                    DocumentType::try_from(extension).ok()
-                } else {
-                    None
-                };
+        for rel_path in changes {
+            documents.retain(|document| !document.rel_source_path.starts_with(rel_path));
+            files.retain(|path, _| !path.starts_with(rel_path));
 
-                if let Some(typ) = typ {
-                    let document = DocumentMetadata::new(&site, entry.path(), typ, modified)?;
-                    documents.push(Arc::new(document));
-                } else {
-                    let rel_file_path = entry.path().strip_prefix(&site.source_path)?;
-                    let content = fs::read(entry.path())?;
-
-                    let file = FileMetadata {
-                        site: site.clone(),
-                        path: rel_file_path.to_owned(),
-                        source_path: entry.path().to_owned(),
-                        content,
-                        modified,
-                    };
-
-                    files.insert(file.path.clone(), file);
-                }
+            let path = site.source_path.join(rel_path);
+            if !is_hidden(rel_path) && path.exists() {
+                scan(&site, &path, &mut documents, &mut files)?;
             }
         }
 
@@ -139,3 +199,252 @@ impl MetadatadSite {
         })
     }
 }
+
+/// Whether a path relative to the site folder is left out of the site, in
+/// which case it can still be a dependency of documents.
+fn is_hidden(rel_path: &Path) -> bool {
+    rel_path.components().any(|component| match component {
+        Component::Normal(name) => name
+            .to_str()
+            .map(|name| name != "_posts" && (name.starts_with(".") || name.starts_with("_")))
+            .unwrap_or(true),
+        _ => true,
+    })
+}
+
+fn scan(
+    site: &Arc<SiteMetadata>,
+    path: &Path,
+    documents: &mut Vec<Arc<DocumentMetadata>>,
+    files: &mut HashMap<PathBuf, FileMetadata>,
+) -> Result<(), Error> {
+    let walker = WalkDir::new(path).into_iter().filter_entry(|entry| {
+        if let Some(file_name) = entry.file_name().to_str() {
+            if file_name == "_posts" && entry.file_type().is_dir() {
+                return true;
+            }
+
+            if file_name.starts_with(".") || file_name.starts_with("_") {
+                return false;
+            }
+
+            return true;
+        }
+
+        false
+    });
+
+    for entry in walker {
+        let entry = entry?;
+
+        if entry.file_type().is_file() {
+            let modified = fs::metadata(entry.path())
+                .and_then(|metadata| metadata.modified())
+                .path_context(entry.path())?;
+
+            let document = if let Some(extension) = entry.path().extension() {
+                let extension = extension
+                    .to_str()
+                    .ok_or_else(|| Error::PathContainNonUnicode(entry.path().to_owned()))?;
+                DocumentMetadata::new(site, entry.path(), extension, modified)?
+            } else {
+                None
+            };
+
+            if let Some(document) = document {
+                documents.push(Arc::new(document));
+            } else {
+                let rel_file_path = entry.path().strip_prefix(&site.source_path)?;
+                let content = fs::read(entry.path()).path_context(entry.path())?;
+
+                let file = FileMetadata {
+                    site: site.clone(),
+                    path: rel_file_path.to_owned(),
+                    source_path: entry.path().to_owned(),
+                    content: Arc::new(content),
+                    modified,
+                };
+
+                files.insert(file.path.clone(), file);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    struct Fixture {
+        root_path: PathBuf,
+        workspace: MetadatadWorkspace,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root_path =
+                env::temp_dir().join(format!("eighty-metadatad-{}-{}", process::id(), name));
+            let _ = fs::remove_dir_all(&root_path);
+
+            let mut fixture = Self {
+                workspace: MetadatadWorkspace {
+                    root_path: root_path.clone(),
+                    config: WorkspaceConfig::default(),
+                    sites: HashMap::new(),
+                },
+                root_path,
+            };
+            fixture.write(
+                "docs/_site.json",
+                r#"{
+                    "title": "Docs",
+                    "url": "https://example.com/",
+                    "baseUrl": "/",
+                    "author": "Example",
+                    "email": "",
+                    "sitemap": { "enable": true, "depth": null }
+                }"#,
+            );
+            fixture.write("docs/index.md", "Home");
+            fixture.write("docs/guide/intro.md", "Intro");
+            fixture.write("docs/guide/setup.md", "Setup");
+            fixture.write("docs/files/paper.txt", "Paper");
+            fixture.write("docs/_inc/header.md", "Header");
+            fixture.write("docs/blog/_posts/2021-05-01-one.md", "One");
+            fixture.workspace = fixture.scan();
+            fixture
+        }
+
+        fn write(&self, path: &str, content: &str) {
+            let path = self.root_path.join(path);
+            fs::create_dir_all(path.parent().expect("fixture paths have a parent; qed"))
+                .expect("temp dir is writable; qed");
+            fs::write(path, content).expect("temp dir is writable; qed");
+        }
+
+        fn scan(&self) -> MetadatadWorkspace {
+            sorted(MetadatadWorkspace::new(&self.root_path).expect("fixture is valid; qed"))
+        }
+
+        /// Rescan `paths`, checking that the result matches a full scan.
+        fn rescan(&mut self, paths: &[&str]) {
+            let changes = WorkspaceChanges::new(paths.iter().map(PathBuf::from).collect());
+            let workspace = sorted(
+                MetadatadWorkspace::new_with_old(&self.root_path, &self.workspace, &changes)
+                    .expect("fixture is valid; qed"),
+            );
+
+            assert_eq!(workspace, self.scan());
+            self.workspace = workspace;
+        }
+
+        fn documents(&self) -> Vec<PathBuf> {
+            self.workspace.sites[&SiteName("docs".to_owned())]
+                .documents
+                .iter()
+                .map(|document| document.rel_source_path.clone())
+                .collect()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root_path);
+        }
+    }
+
+    fn sorted(mut workspace: MetadatadWorkspace) -> MetadatadWorkspace {
+        for site in workspace.sites.values_mut() {
+            site.documents
+                .sort_by(|a, b| a.rel_source_path.cmp(&b.rel_source_path));
+        }
+        workspace
+    }
+
+    #[test]
+    fn rescan_added_paths() {
+        let mut fixture = Fixture::new("added");
+
+        fixture.write("docs/guide/advanced.md", "Advanced");
+        fixture.write("docs/files/slides.txt", "Slides");
+        fixture.rescan(&["docs/guide/advanced.md", "docs/files/slides.txt"]);
+
+        fixture.write("docs/api/index.md", "API");
+        fixture.write("docs/api/v1.md", "V1");
+        fixture.rescan(&["docs/api"]);
+
+        assert!(fixture.documents().contains(&PathBuf::from("api/v1.md")));
+    }
+
+    #[test]
+    fn rescan_modified_paths() {
+        let mut fixture = Fixture::new("modified");
+
+        fixture.write("docs/guide/intro.md", "Introduction");
+        fixture.write("docs/files/paper.txt", "Paper, revised");
+        fixture.rescan(&["docs/guide/intro.md", "docs/files/paper.txt"]);
+    }
+
+    #[test]
+    fn rescan_removed_paths() {
+        let mut fixture = Fixture::new("removed");
+
+        fs::remove_file(fixture.root_path.join("docs/guide/setup.md"))
+            .expect("temp dir is writable; qed");
+        fixture.rescan(&["docs/guide/setup.md"]);
+
+        fs::remove_dir_all(fixture.root_path.join("docs/guide"))
+            .expect("temp dir is writable; qed");
+        fixture.rescan(&["docs/guide"]);
+
+        assert!(!fixture
+            .documents()
+            .contains(&PathBuf::from("guide/intro.md")));
+    }
+
+    #[test]
+    fn rescan_hidden_and_posts_paths() {
+        let mut fixture = Fixture::new("hidden");
+
+        fixture.write("docs/_inc/header.md", "New header");
+        fixture.write("docs/_inc/footer.md", "Footer");
+        fixture.write("docs/.notes.md", "Notes");
+        fixture.rescan(&[
+            "docs/_inc/header.md",
+            "docs/_inc/footer.md",
+            "docs/.notes.md",
+        ]);
+
+        assert!(!fixture
+            .documents()
+            .contains(&PathBuf::from("_inc/footer.md")));
+
+        fixture.write("docs/blog/_posts/2022-01-03-two.md", "Two");
+        fixture.rescan(&["docs/blog/_posts/2022-01-03-two.md"]);
+
+        assert!(fixture
+            .documents()
+            .contains(&PathBuf::from("blog/_posts/2022-01-03-two.md")));
+    }
+
+    #[test]
+    fn rescan_site_config() {
+        let mut fixture = Fixture::new("site-config");
+
+        fixture.write(
+            "docs/_site.json",
+            r#"{
+                "title": "Documentation",
+                "url": "https://example.com/",
+                "baseUrl": "/",
+                "author": "Example",
+                "email": "",
+                "sitemap": { "enable": true, "depth": null }
+            }"#,
+        );
+        fixture.rescan(&["docs/_site.json"]);
+    }
+}
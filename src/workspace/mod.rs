@@ -16,13 +16,18 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+mod changes;
+mod config;
 mod full;
+mod listing;
 mod metadatad;
 mod post;
 mod rendered;
 
 pub use self::{
-    full::{FullDocument, FullSite, FullWorkspace},
+    changes::WorkspaceChanges,
+    config::WorkspaceConfig,
+    full::{FullDocument, FullSite, FullWorkspace, NotFoundPage},
     metadatad::{MetadatadSite, MetadatadWorkspace},
     post::{SimplePostSite, SimplePostWorkspace},
     rendered::{RenderedSite, RenderedWorkspace},
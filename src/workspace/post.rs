@@ -16,12 +16,19 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use super::FullWorkspace;
-use crate::{site::SiteName, Error};
+use super::{FullWorkspace, NotFoundPage};
+use crate::{
+    crawler::{self, SitemapEntry},
+    feed::{self, FeedFormat, FeedItem},
+    site::SiteName,
+    xref::{XrefKind, XrefTarget},
+    Error,
+};
 use std::{
     collections::HashMap,
     ops::Deref,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 pub struct SimplePostWorkspace(pub HashMap<SiteName, SimplePostSite>);
@@ -42,9 +49,82 @@ impl SimplePostWorkspace {
             }
 
             for document in &full_site.documents {
-                post_site.insert(document.rendered.name.path(), document.content.as_bytes().to_owned());
+                post_site.insert(
+                    document.rendered.name.path(),
+                    Arc::new(document.content.as_bytes().to_owned()),
+                );
+            }
+
+            if let Some(not_found) = &full_site.not_found {
+                insert_not_found(&mut post_site, site_name, not_found);
             }
 
+            // Files of the site itself take precedence.
+            for (listing_path, listing) in &full_site.listings {
+                post_site
+                    .entry(listing_path.clone())
+                    .or_insert_with(|| Arc::new(listing.as_bytes().to_owned()));
+            }
+
+            let config = &full_site.site.config;
+            for feed in feed::feeds(config, &full_site.sitemap) {
+                let mut posts = full_site
+                    .documents
+                    .iter()
+                    .filter(|document| feed.contains(&document.rendered.name))
+                    .collect::<Vec<_>>();
+                // Newest first.
+                posts.sort_by(|a, b| b.rendered.name.post.cmp(&a.rendered.name.post));
+
+                let items = posts
+                    .into_iter()
+                    .take(config.feeds.limit)
+                    .map(|document| FeedItem {
+                        title: document.rendered.title.clone(),
+                        url: XrefTarget::Document(document.rendered.name.clone())
+                            .full_url(XrefKind::FullLink, config),
+                        date: document
+                            .rendered
+                            .name
+                            .post
+                            .as_ref()
+                            .map(|post| post.date.clone())
+                            .unwrap_or_default(),
+                        summary: document.rendered.description.clone(),
+                        content: document.post_content.clone().unwrap_or_default(),
+                    })
+                    .collect::<Vec<_>>();
+
+                for format in FeedFormat::enabled(config) {
+                    post_site.entry(feed.path(format)).or_insert_with(|| {
+                        Arc::new(feed::render(format, config, &feed, &items).into_bytes())
+                    });
+                }
+            }
+
+            let mut entries = full_site
+                .documents
+                .iter()
+                .filter(|document| !document.rendered.is_noindex())
+                .map(|document| SitemapEntry {
+                    url: XrefTarget::Document(document.rendered.name.clone())
+                        .full_url(XrefKind::FullLink, config),
+                    modified: Some(document.metadata.modified),
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.url.cmp(&b.url));
+            post_site
+                .entry(Path::new("sitemap.xml").to_owned())
+                .or_insert_with(|| Arc::new(crawler::sitemap_xml(&entries).into_bytes()));
+            post_site
+                .entry(Path::new("robots.txt").to_owned())
+                .or_insert_with(|| {
+                    let sitemap_url = XrefTarget::File(Path::new("sitemap.xml").to_owned())
+                        .full_url(XrefKind::FullLink, config);
+                    let robots = crawler::robots_txt(&config.robots.disallow, Some(&sitemap_url));
+                    Arc::new(robots.into_bytes())
+                });
+
             sites.insert(
                 site_name.clone(),
                 SimplePostSite {
@@ -57,14 +137,43 @@ impl SimplePostWorkspace {
         let mut spec_site_files = HashMap::new();
         spec_site_files.insert(
             Path::new("index.html").to_owned(),
-            full.spec_site.index_content.as_bytes().to_owned(),
+            Arc::new(full.spec_site.index_content.as_bytes().to_owned()),
         );
-        for (_, spec) in &full.spec_site.specs {
+        for spec in full.spec_site.specs.values() {
             spec_site_files.insert(
                 spec.path().clone(),
-                spec.redirect_content.as_bytes().to_owned(),
+                Arc::new(spec.redirect_content.as_bytes().to_owned()),
             );
         }
+        let spec_sitemap_url = full.spec_site.url.as_ref().map(|url| {
+            let mut entries = full
+                .spec_site
+                .specs
+                .values()
+                .map(|spec| SitemapEntry {
+                    url: format!("{}{}/", url, spec.folder_path().display()),
+                    modified: None,
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.url.cmp(&b.url));
+            entries.insert(
+                0,
+                SitemapEntry {
+                    url: url.clone(),
+                    modified: None,
+                },
+            );
+            spec_site_files.insert(
+                Path::new("sitemap.xml").to_owned(),
+                Arc::new(crawler::sitemap_xml(&entries).into_bytes()),
+            );
+
+            format!("{}sitemap.xml", url)
+        });
+        spec_site_files.insert(
+            Path::new("robots.txt").to_owned(),
+            Arc::new(crawler::robots_txt(&[], spec_sitemap_url.as_deref()).into_bytes()),
+        );
         sites.insert(
             SiteName("specs".into()),
             SimplePostSite {
@@ -77,6 +186,36 @@ impl SimplePostWorkspace {
     }
 }
 
+/// Add the page served for missing paths as `404.html`. A `404` document
+/// replaces a `404.html` file of the site, but a page from the layout alone
+/// does not.
+fn insert_not_found(
+    files: &mut HashMap<PathBuf, Arc<Vec<u8>>>,
+    site_name: &SiteName,
+    not_found: &NotFoundPage,
+) {
+    let path = Path::new("404.html").to_owned();
+
+    match not_found {
+        NotFoundPage::Document(content) => {
+            if files
+                .insert(path, Arc::new(content.as_bytes().to_owned()))
+                .is_some()
+            {
+                println!(
+                    "[{}] warning: 404.html: replaced by the 404 document",
+                    site_name
+                );
+            }
+        }
+        NotFoundPage::Layout(content) => {
+            files
+                .entry(path)
+                .or_insert_with(|| Arc::new(content.as_bytes().to_owned()));
+        }
+    }
+}
+
 impl Deref for SimplePostWorkspace {
     type Target = HashMap<SiteName, SimplePostSite>;
 
@@ -87,5 +226,57 @@ impl Deref for SimplePostWorkspace {
 
 pub struct SimplePostSite {
     pub base_url: String,
-    pub files: HashMap<PathBuf, Vec<u8>>,
+    pub files: HashMap<PathBuf, Arc<Vec<u8>>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(content: &str) -> HashMap<PathBuf, Arc<Vec<u8>>> {
+        HashMap::from([(
+            PathBuf::from("404.html"),
+            Arc::new(content.as_bytes().to_owned()),
+        )])
+    }
+
+    fn not_found_content(files: &HashMap<PathBuf, Arc<Vec<u8>>>) -> &[u8] {
+        files[Path::new("404.html")].as_slice()
+    }
+
+    #[test]
+    fn static_not_found_is_kept_over_layout() {
+        let mut files = files("static");
+        insert_not_found(
+            &mut files,
+            &SiteName("docs".to_owned()),
+            &NotFoundPage::Layout("layout".to_owned()),
+        );
+
+        assert_eq!(not_found_content(&files), b"static");
+    }
+
+    #[test]
+    fn not_found_document_replaces_static() {
+        let mut files = files("static");
+        insert_not_found(
+            &mut files,
+            &SiteName("docs".to_owned()),
+            &NotFoundPage::Document("document".to_owned()),
+        );
+
+        assert_eq!(not_found_content(&files), b"document");
+    }
+
+    #[test]
+    fn layout_not_found_without_static() {
+        let mut files = HashMap::new();
+        insert_not_found(
+            &mut files,
+            &SiteName("docs".to_owned()),
+            &NotFoundPage::Layout("layout".to_owned()),
+        );
+
+        assert_eq!(not_found_content(&files), b"layout");
+    }
 }
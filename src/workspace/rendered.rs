@@ -16,12 +16,13 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use super::{MetadatadSite, MetadatadWorkspace};
+use super::{MetadatadSite, MetadatadWorkspace, WorkspaceConfig};
 use crate::{
-    document::RenderedDocument,
+    cache::RenderCache,
+    document::{DocumentMetadata, RenderedDocument},
     file::FileMetadata,
     site::{SiteMetadata, SiteName},
-    Error,
+    Error, ErrorContext,
 };
 use rayon::prelude::*;
 use std::{collections::HashMap, path::PathBuf, sync::Arc};
@@ -29,19 +30,38 @@ use std::{collections::HashMap, path::PathBuf, sync::Arc};
 #[derive(Eq, Clone, PartialEq, Debug)]
 pub struct RenderedWorkspace {
     pub root_path: PathBuf,
+    pub config: WorkspaceConfig,
     pub sites: HashMap<SiteName, RenderedSite>,
 }
 
 impl RenderedWorkspace {
     pub fn new(metadata: &MetadatadWorkspace) -> Result<RenderedWorkspace, Error> {
+        let cache = RenderCache::new(&metadata.root_path);
+
         let sites = metadata
             .sites
             .par_iter()
-            .map(|(name, site)| Ok((name.clone(), RenderedSite::new(&site)?)))
+            .map(|(name, site)| {
+                Ok((
+                    name.clone(),
+                    RenderedSite::new(site, &cache).site_context(name)?,
+                ))
+            })
             .collect::<Result<_, Error>>()?;
 
+        if cache.hits() > 0 {
+            println!(
+                "[workspace] {} document(s) reused from render cache",
+                cache.hits()
+            );
+        }
+        if let Err(err) = cache.prune() {
+            println!("warning: failed to prune render cache: {}", err);
+        }
+
         Ok(Self {
             sites,
+            config: metadata.config.clone(),
             root_path: metadata.root_path.clone(),
         })
     }
@@ -50,20 +70,25 @@ impl RenderedWorkspace {
         metadata: &MetadatadWorkspace,
         old: &RenderedWorkspace,
     ) -> Result<RenderedWorkspace, Error> {
+        let cache = RenderCache::new(&metadata.root_path);
+
         let sites = metadata
             .sites
             .par_iter()
             .map(|(name, site)| {
-                if let Some(old_site) = old.sites.get(&name) {
-                    Ok((name.clone(), RenderedSite::new_with_old(&site, old_site)?))
+                let site = if let Some(old_site) = old.sites.get(name) {
+                    RenderedSite::new_with_old(site, old_site, &cache)
                 } else {
-                    Ok((name.clone(), RenderedSite::new(&site)?))
-                }
+                    RenderedSite::new(site, &cache)
+                };
+
+                Ok((name.clone(), site.site_context(name)?))
             })
             .collect::<Result<_, Error>>()?;
 
         Ok(Self {
             sites,
+            config: metadata.config.clone(),
             root_path: metadata.root_path.clone(),
         })
     }
@@ -77,14 +102,15 @@ pub struct RenderedSite {
 }
 
 impl RenderedSite {
-    pub fn new(metadata: &MetadatadSite) -> Result<RenderedSite, Error> {
+    pub fn new(metadata: &MetadatadSite, cache: &RenderCache) -> Result<RenderedSite, Error> {
         let documents = metadata
             .documents
             .par_iter()
             .map(|document| {
                 Ok((
                     document.rel_source_path.clone(),
-                    RenderedDocument::new(metadata.site.clone(), document.clone())?,
+                    render_document(&metadata.site, document, cache)
+                        .path_context(&document.rel_source_path)?,
                 ))
             })
             .collect::<Result<_, Error>>()?;
@@ -99,6 +125,7 @@ impl RenderedSite {
     pub fn new_with_old(
         metadata: &MetadatadSite,
         old: &RenderedSite,
+        cache: &RenderCache,
     ) -> Result<RenderedSite, Error> {
         let documents = metadata
             .documents
@@ -107,6 +134,7 @@ impl RenderedSite {
                 if let Some(old_document) = old.documents.get(&document.rel_source_path) {
                     if old_document.site_metadata == metadata.site
                         && old_document.metadata == *document
+                        && old_document.data.is_current(&metadata.site.source_path)
                     {
                         return Ok((document.rel_source_path.clone(), old_document.clone()));
                     }
@@ -114,7 +142,8 @@ impl RenderedSite {
 
                 Ok((
                     document.rel_source_path.clone(),
-                    RenderedDocument::new(metadata.site.clone(), document.clone())?,
+                    render_document(&metadata.site, document, cache)
+                        .path_context(&document.rel_source_path)?,
                 ))
             })
             .collect::<Result<_, Error>>()?;
@@ -126,3 +155,29 @@ impl RenderedSite {
         })
     }
 }
+
+fn render_document(
+    site: &Arc<SiteMetadata>,
+    document: &Arc<DocumentMetadata>,
+    cache: &RenderCache,
+) -> Result<RenderedDocument, Error> {
+    let key = cache.key(site, document)?;
+
+    if let Some(data) = cache.get(&key, site)? {
+        return Ok(RenderedDocument {
+            site_metadata: site.clone(),
+            metadata: document.clone(),
+            data: Arc::new(data),
+        });
+    }
+
+    let rendered = RenderedDocument::new(site.clone(), document.clone())?;
+    if let Err(err) = cache.put(&key, &rendered.data) {
+        println!(
+            "[{}] warning: failed to write render cache: {}",
+            site.name, err
+        );
+    }
+
+    Ok(rendered)
+}
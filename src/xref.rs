@@ -0,0 +1,255 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    document::DocumentName,
+    site::{SiteConfig, SiteMetadata, SiteName},
+    workspace::RenderedSite,
+    Error,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Eq, Clone, Copy, PartialEq, Debug)]
+pub enum XrefKind {
+    Link,
+    Path,
+    FullLink,
+}
+
+impl<'a> TryFrom<&'a str> for XrefKind {
+    type Error = ();
+
+    fn try_from(s: &'a str) -> Result<XrefKind, ()> {
+        match s {
+            "XREFLINK" => Ok(XrefKind::Link),
+            "XREFPATH" => Ok(XrefKind::Path),
+            "XREFFULLLINK" => Ok(XrefKind::FullLink),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Eq, Clone, PartialEq, Debug)]
+pub enum XrefTarget {
+    Document(DocumentName),
+    File(PathBuf),
+}
+
+impl XrefTarget {
+    pub fn link(&self) -> String {
+        match self {
+            XrefTarget::Document(name) if name.is_root() => String::new(),
+            XrefTarget::Document(name) => format!("{}/", name.folder_path().display()),
+            XrefTarget::File(path) => format!("{}", path.display()),
+        }
+    }
+
+    pub fn path(&self) -> String {
+        match self {
+            XrefTarget::Document(name) => format!("{}", name.path().display()),
+            XrefTarget::File(path) => format!("{}", path.display()),
+        }
+    }
+
+    pub fn url(&self, kind: XrefKind, config: &SiteConfig) -> String {
+        match kind {
+            XrefKind::Link => format!("{}{}", config.base_url, self.link()),
+            XrefKind::Path => format!("{}{}", config.base_url, self.path()),
+            XrefKind::FullLink => format!("{}{}", config.url, self.link()),
+        }
+    }
+
+    /// Like `url`, but always includes the domain name, for linking from
+    /// another site.
+    pub fn full_url(&self, kind: XrefKind, config: &SiteConfig) -> String {
+        match kind {
+            XrefKind::Link | XrefKind::FullLink => format!("{}{}", config.url, self.link()),
+            XrefKind::Path => format!("{}{}", config.url, self.path()),
+        }
+    }
+}
+
+#[derive(Eq, Clone, PartialEq, Debug)]
+pub struct SiteXrefs {
+    pub site: Arc<SiteMetadata>,
+    pub targets: HashMap<PathBuf, XrefTarget>,
+}
+
+impl SiteXrefs {
+    pub fn new(rendered: &RenderedSite) -> Result<Self, Error> {
+        let mut targets = HashMap::new();
+
+        for document in rendered.documents.values() {
+            let rel_path = document
+                .metadata
+                .source_path
+                .strip_prefix(&rendered.site.source_path)?;
+            // The 404 document is only written out as `404.html`.
+            let target = if document.data.name.is_not_found() {
+                XrefTarget::File(PathBuf::from("404.html"))
+            } else {
+                XrefTarget::Document(document.data.name.clone())
+            };
+            targets.insert(rel_path.to_owned(), target);
+        }
+        for rel_path in rendered.files.keys() {
+            targets.insert(rel_path.clone(), XrefTarget::File(rel_path.clone()));
+        }
+
+        Ok(Self {
+            site: rendered.site.clone(),
+            targets,
+        })
+    }
+}
+
+/// Split macro arguments into an optional site name and the target path. A
+/// site-qualified target is written as `site:path/to/doc.adoc`.
+pub fn parse(arguments: &str) -> (Option<SiteName>, &Path) {
+    let mut splited = arguments.splitn(2, ':');
+    let first = splited.next().expect("will return at least one item; qed");
+
+    match splited.next() {
+        Some(path) => (Some(SiteName(first.to_owned())), Path::new(path)),
+        None => (None, Path::new(first)),
+    }
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, PartialOrd, Ord)]
+pub struct UnresolvedXref {
+    pub site: SiteName,
+    pub document: PathBuf,
+    pub variable: String,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for UnresolvedXref {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}: unresolved {}",
+            self.site,
+            self.document.display(),
+            self.variable
+        )?;
+        if let Some(suggestion) = self.suggestion.as_ref() {
+            write!(f, " (did you mean {}?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the closest existing target for unresolved macro arguments, in the
+/// same `site:path` form as the arguments.
+pub fn suggest(
+    workspace_xrefs: &HashMap<SiteName, SiteXrefs>,
+    site_name: &SiteName,
+    arguments: &str,
+) -> Option<String> {
+    match parse(arguments) {
+        (Some(target_site), path) => match workspace_xrefs.get(&target_site) {
+            Some(site_xrefs) => closest(path, site_xrefs.targets.keys())
+                .map(|path| format!("{}:{}", target_site, path.display())),
+            None => closest_str(&target_site.0, workspace_xrefs.keys().map(|name| &name.0))
+                .map(|name| format!("{}:{}", name, path.display())),
+        },
+        (None, path) => workspace_xrefs
+            .get(site_name)
+            .and_then(|site_xrefs| closest(path, site_xrefs.targets.keys()))
+            .map(|path| format!("{}", path.display())),
+    }
+}
+
+/// Find the closest macro name for an unknown one, such as `XREFLINK` for
+/// `XREFLNK`.
+pub fn suggest_kind(name: &str) -> Option<String> {
+    let names = ["XREFLINK", "XREFPATH", "XREFFULLLINK"].map(String::from);
+
+    closest_str(name, names.iter()).cloned()
+}
+
+fn closest<'a>(path: &Path, candidates: impl Iterator<Item = &'a PathBuf>) -> Option<&'a PathBuf> {
+    let path = path.to_string_lossy();
+
+    candidates
+        .map(|candidate| {
+            (
+                strsim::levenshtein(&path, &candidate.to_string_lossy()),
+                candidate,
+            )
+        })
+        .filter(|(distance, _)| is_close(*distance, &path))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+fn closest_str<'a>(s: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a String> {
+    candidates
+        .map(|candidate| (strsim::levenshtein(s, candidate), candidate))
+        .filter(|(distance, _)| is_close(*distance, s))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+fn is_close(distance: usize, s: &str) -> bool {
+    distance <= usize::max(3, s.chars().count() / 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_local_path() {
+        assert_eq!(
+            parse("guide/intro.adoc"),
+            (None, Path::new("guide/intro.adoc"))
+        );
+    }
+
+    #[test]
+    fn parse_site_qualified_path() {
+        assert_eq!(
+            parse("docs:guide/intro.adoc"),
+            (
+                Some(SiteName("docs".to_owned())),
+                Path::new("guide/intro.adoc")
+            )
+        );
+    }
+
+    #[test]
+    fn parse_splits_on_first_colon_only() {
+        assert_eq!(
+            parse("docs:a:b.adoc"),
+            (Some(SiteName("docs".to_owned())), Path::new("a:b.adoc"))
+        );
+    }
+
+    #[test]
+    fn suggest_kind_for_typos() {
+        assert_eq!(suggest_kind("XREFLNK"), Some("XREFLINK".to_owned()));
+        assert_eq!(suggest_kind("XREFFULLINK"), Some("XREFFULLLINK".to_owned()));
+        assert_eq!(suggest_kind("TABLEOFCONTENTS"), None);
+    }
+}